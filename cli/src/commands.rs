@@ -0,0 +1,334 @@
+use {
+    anchor_lang::{prelude::Pubkey, AccountDeserialize},
+    anchor_spl::token_interface::Mint,
+    anyhow::{anyhow, bail, Context, Result},
+    raffler_anchor::{randomness::{ENTROPY_DELAY, REVEAL_TIMEOUT}, state::*},
+    raffler_cli::{inspect, spec::*},
+    raffler_client::{accounts::*, instructions::*, pda::*, Purchase, RaffleKeys, TicketAccount, ID},
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{
+        instruction::Instruction,
+        signature::{Keypair, Signature},
+        signer::Signer,
+        transaction::Transaction,
+    },
+    std::{fs, path::Path, thread, time::{Duration, SystemTime, UNIX_EPOCH}},
+};
+
+// set_winner can come back without a winner when every attempt hit a ticket that already won
+const MAX_EMPTY_DRAWS: u32 = 16;
+
+pub struct Operator {
+    pub rpc: RpcClient,
+    pub payer: Keypair,
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+fn secret_path(raffle: &Pubkey) -> String {
+    format!("{}.secret", raffle)
+}
+
+impl Operator {
+    // the wallet pays fees and signs, anything else is listed in `signers`
+    fn send(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Signature> {
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend_from_slice(signers);
+
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(instructions, Some(&self.payer.pubkey()), &all_signers, blockhash);
+
+        Ok(self.rpc.send_and_confirm_transaction(&tx)?)
+    }
+
+    fn token_program(&self, mint: &Pubkey) -> Result<Pubkey> {
+        Ok(self.rpc.get_account(mint).with_context(|| format!("fetching mint {}", mint))?.owner)
+    }
+
+    fn decimals(&self, mint: &Pubkey) -> Result<u8> {
+        let data = self.rpc.get_account_data(mint)?;
+        Ok(Mint::try_deserialize(&mut &data[..]).with_context(|| format!("{} isn't a mint", mint))?.decimals)
+    }
+
+    fn raffle(&self, raffle: &Pubkey) -> Result<RaffleAccount> {
+        let data = self.rpc.get_account_data(raffle).with_context(|| format!("fetching raffle {}", raffle))?;
+        decode_raffle(&data).map_err(|err| anyhow!("{} isn't a raffle: {}", raffle, err))
+    }
+
+    // raffles from main.js or this tool sit at the derived address, anything else is looked up by its raffle link
+    fn find_tickets(&self, raffle: &Pubkey) -> Result<Pubkey> {
+        let derived = ticket_keypair(raffle).pubkey();
+
+        if let Ok(data) = self.rpc.get_account_data(&derived) {
+            if decode_tickets(&data).is_ok_and(|tickets| tickets.raffle == *raffle) {
+                return Ok(derived);
+            }
+        }
+
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, <FixedTicketAccount as anchor_lang::Discriminator>::DISCRIMINATOR.to_vec())),
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(TICKETS_RAFFLE_OFFSET, raffle.to_bytes().to_vec())),
+            ]),
+            account_config: RpcAccountInfoConfig::default(),
+            ..RpcProgramAccountsConfig::default()
+        };
+
+        let found = self.rpc.get_program_accounts_with_config(&ID, config)?;
+
+        found.first().map(|(key, _)| *key).ok_or_else(|| anyhow!("no ticket account for raffle {}", raffle))
+    }
+
+    fn keys(&self, raffle: &Pubkey, account: &RaffleAccount) -> Result<RaffleKeys> {
+        let tickets = self.find_tickets(raffle)?;
+
+        Ok(RaffleKeys::from_account(raffle, account, &tickets)
+            .with_token_programs(&self.token_program(&account.mint)?, &self.token_program(&account.prize)?))
+    }
+
+    fn tickets(&self, keys: &RaffleKeys) -> Result<TicketAccount> {
+        let data = self.rpc.get_account_data(&keys.tickets)?;
+        decode_tickets(&data).map_err(|err| anyhow!("bad ticket account {}: {}", keys.tickets, err))
+    }
+
+    // the wallet is the owner, the ticket account and the owner's ATAs come with it
+    pub fn create(&self, spec_path: &Path) -> Result<()> {
+        let spec = RaffleSpec::load(spec_path)?;
+        let owner = self.payer.pubkey();
+        let (mint_cost, mint_prize) = (spec.mint_cost()?, spec.mint_prize()?);
+        let now = now();
+        let nonce = spec.nonce.unwrap_or(now as u64);
+
+        let keys = RaffleKeys::new(&owner, &mint_cost, &mint_prize, nonce, spec.native)
+            .with_token_programs(&self.token_program(&mint_cost)?, &self.token_program(&mint_prize)?);
+
+        let (secret, generated) = match spec.secret()? {
+            Some(secret) => (secret, false),
+            None => (rand::random(), true),
+        };
+
+        let data = spec.data(nonce, now, (self.decimals(&mint_cost)?, self.decimals(&mint_prize)?), &secret)?;
+
+        // written before anything is sent, a raffle nobody can reveal only draws after the timeout
+        if generated {
+            fs::write(secret_path(&keys.raffle), to_hex(&secret))?;
+        }
+
+        let tickets = ticket_keypair(&keys.raffle);
+        // native raffles have no cost ATAs for init_token_accounts to make, only the prize escrow has to exist
+        let mut instructions = vec![if spec.native {
+            create_token_account(&owner, &keys.raffle, &mint_prize, &keys.prize_token_program)
+        } else {
+            init_token_accounts(&keys, &owner, &owner)
+        }];
+
+        instructions.extend([create_ticket_account(&keys), create_raffle(&keys, data, spec.prize_metadata()?)]);
+
+        let signature = self.send(&instructions, &[&tickets])?;
+
+        println!("raffle   {}", keys.raffle);
+        println!("tickets  {}", keys.tickets);
+
+        if generated {
+            println!("secret   {} (keep it, draw reveals it)", secret_path(&keys.raffle));
+        }
+
+        println!("tx       {}", signature);
+
+        Ok(())
+    }
+
+    pub fn buy(&self, raffle: &Pubkey, purchase: &Purchase) -> Result<()> {
+        let account = self.raffle(raffle)?;
+        let keys = self.keys(raffle, &account)?;
+
+        let signature = self.send(&[buy_ticket(&keys, &self.payer.pubkey(), purchase)], &[])?;
+
+        println!("bought {} tickets, tx {}", purchase.amount, signature);
+
+        Ok(())
+    }
+
+    // locks, reveals or requests the oracle's randomness, and cranks set_winner until every winner is in
+    pub fn draw(&self, raffle: &Pubkey, secret: Option<[u8; 32]>) -> Result<()> {
+        let mut account = self.raffle(raffle)?;
+        let keys = self.keys(raffle, &account)?;
+
+        if account.locked_at == 0 {
+            self.send(&[lock_raffle(&keys, &self.payer.pubkey())], &[])?;
+            account = self.raffle(raffle)?;
+            println!("locked at {}", account.locked_at);
+        }
+
+        if !account.revealed && account.source == RandomnessSource::SlotHash {
+            let secret = match secret {
+                Some(secret) => Some(secret),
+                None => fs::read_to_string(secret_path(raffle)).ok().map(|hex| parse_hex32(&hex)).transpose()?,
+            };
+
+            match secret {
+                Some(secret) => {
+                    // the reveal mixes in the hash of a slot after the lock, so that slot has to pass first
+                    while self.rpc.get_slot()? <= account.lock_slot + ENTROPY_DELAY {
+                        thread::sleep(Duration::from_millis(400));
+                    }
+
+                    self.send(&[reveal_secret(&keys, secret)], &[])?;
+                    println!("revealed");
+                }
+                None if now() < account.locked_at + REVEAL_TIMEOUT => {
+                    // keeping the slot hash on the raffle leaves whoever has the secret the rest of the timeout
+                    if account.slot_hash == [0; 32] && self.rpc.get_slot()? > account.lock_slot + ENTROPY_DELAY && self.send(&[record_slot_hash(&keys, &self.payer.pubkey())], &[]).is_ok() {
+                        println!("recorded the slot hash");
+                    }
+
+                    bail!("no secret for {}, pass --secret, without a reveal the raffle refunds after the timeout", raffle);
+                }
+                None => println!("never revealed, sending the raffle to refunds"),
+            }
+        }
+
+        // the oracle signs off chain, so this requests and waits, running draw again picks up once it has fulfilled
+        if !account.revealed && account.source == RandomnessSource::Oracle {
+            let address = randomness_address(raffle);
+
+            if self.rpc.get_account(&address).is_err() {
+                self.send(&[request_randomness(&keys, &self.payer.pubkey())], &[])?;
+                println!("requested randomness from oracle {}", account.oracle);
+            }
+
+            let request: RandomnessRequest = decode(&self.rpc.get_account_data(&address)?).map_err(|err| anyhow!("bad randomness request {}: {}", address, err))?;
+
+            if request.fulfilled {
+                // the seed mixes in the hash of a slot after the fulfilment
+                while self.rpc.get_slot()? <= request.fulfilled_slot + ENTROPY_DELAY {
+                    thread::sleep(Duration::from_millis(400));
+                }
+            } else if now() < account.locked_at + REVEAL_TIMEOUT {
+                bail!("oracle {} hasn't fulfilled {} yet, run draw again once it has", account.oracle, address);
+            } else {
+                println!("the oracle never fulfilled, sending the raffle to refunds");
+            }
+        }
+
+        let mut empty = 0;
+
+        loop {
+            let account = self.raffle(raffle)?;
+
+            if account.refunding {
+                println!("refunding, buyers can claim_refund");
+                return Ok(());
+            }
+
+            if account.winners_selected {
+                println!("{} winners selected", account.winners.len());
+                return Ok(());
+            }
+
+            if empty == MAX_EMPTY_DRAWS {
+                bail!("no new winner after {} draws", MAX_EMPTY_DRAWS);
+            }
+
+            self.send(&[set_winner(&keys, &self.payer.pubkey())], &[])?;
+
+            if self.raffle(raffle)?.winners.len() > account.winners.len() {
+                empty = 0;
+                println!("winner {} of {}", account.winners.len() + 1, account.winner_count());
+            } else {
+                empty += 1;
+            }
+        }
+    }
+
+    // draw_winner pays the last unpaid slot, so this works backwards through the winners
+    pub fn pay(&self, raffle: &Pubkey) -> Result<()> {
+        let mut account = self.raffle(raffle)?;
+        let keys = self.keys(raffle, &account)?;
+        let tickets = self.tickets(&keys)?;
+
+        if account.owner != self.payer.pubkey() {
+            bail!("only the owner {} can pay winners, they can still claim_prize themselves", account.owner);
+        }
+
+        if !account.winners_selected {
+            bail!("winners aren't selected yet, run draw first");
+        }
+
+        while let Some(slot) = account.claimed.iter().rposition(|claimed| !claimed) {
+            let ticket = account.winners[slot];
+            let winner = tickets.buyer(ticket).ok_or_else(|| anyhow!("ticket {} has no buyer", ticket))?;
+            let prize = account.slot_prize(slot).ok_or_else(|| anyhow!("slot {} has no prize", slot))?;
+            let token_program = self.token_program(&prize.mint)?;
+
+            let signature = self.send(&[
+                create_token_account(&self.payer.pubkey(), &winner, &prize.mint, &token_program),
+                draw_winner(&keys, &winner, &prize.mint, &token_program),
+            ], &[])?;
+
+            println!("paid slot {} ticket {} to {}, tx {}", slot, ticket, winner, signature);
+
+            account = self.raffle(raffle)?;
+        }
+
+        println!("every winner is paid");
+
+        Ok(())
+    }
+
+    pub fn close(&self, raffle: &Pubkey, force_close: bool) -> Result<()> {
+        let account = self.raffle(raffle)?;
+        let keys = self.keys(raffle, &account)?;
+        let config: ProtocolConfig = decode(&self.rpc.get_account_data(&config_address())?).map_err(|err| anyhow!("bad config: {}", err))?;
+        let payer = self.payer.pubkey();
+
+        let mut instructions = vec![];
+
+        // extra tiers are returned and their escrows closed on their own, close_raffle only takes the main prize
+        for (tier, prize) in account.tiers.iter().enumerate().skip(1).filter(|(_, prize)| !prize.closed) {
+            let token_program = self.token_program(&prize.mint)?;
+
+            instructions.push(create_token_account(&payer, &keys.owner, &prize.mint, &token_program));
+            instructions.push(close_prize_tier(&keys, &payer, &config.fee_recipient, tier as u8, &prize.mint, &token_program, force_close));
+        }
+
+        // spl proceeds and the fee are paid into ATAs that might not be around yet
+        if !keys.native {
+            for owner in [keys.owner, config.fee_recipient] {
+                instructions.push(create_token_account(&payer, &owner, &keys.mint_cost, &keys.cost_token_program));
+            }
+        }
+
+        instructions.push(close_raffle(&keys, &payer, &config.fee_recipient, force_close, &account.bundle));
+
+        let signature = self.send(&instructions, &[])?;
+
+        println!("closed {}, tx {}", raffle, signature);
+
+        Ok(())
+    }
+
+    pub fn show(&self, raffle: &Pubkey) -> Result<()> {
+        let account = self.raffle(raffle)?;
+        let keys = self.keys(raffle, &account)?;
+        let tickets = self.tickets(&keys)?;
+
+        print!("{}", inspect::render(raffle, &keys.tickets, &account, &tickets, now()));
+
+        Ok(())
+    }
+
+    // the raffle and its ticket account as they are now, for verify
+    pub fn snapshot(&self, raffle: &Pubkey) -> Result<(RaffleAccount, TicketAccount)> {
+        let account = self.raffle(raffle)?;
+        let tickets = self.tickets(&RaffleKeys::from_account(raffle, &account, &self.find_tickets(raffle)?))?;
+
+        Ok((account, tickets))
+    }
+}
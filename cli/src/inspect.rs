@@ -0,0 +1,142 @@
+use {
+    anchor_lang::prelude::Pubkey,
+    raffler_anchor::state::*,
+    raffler_client::{verify::Verification, TicketAccount},
+    crate::spec::to_hex,
+};
+
+// where the raffle is in its lifecycle, going by the account and the local clock
+pub fn status(raffle: &RaffleAccount, now: i64) -> &'static str {
+    if raffle.refunding {
+        "refunding"
+    } else if raffle.winners_selected && raffle.claimed.iter().all(|claimed| *claimed) {
+        "paid out"
+    } else if raffle.winners_selected {
+        "winners selected"
+    } else if raffle.revealed {
+        "drawing"
+    } else if raffle.locked_at != 0 {
+        "locked"
+    } else if now < raffle.start {
+        "not started"
+    } else if now < raffle.end && raffle.tickets_purchased < raffle.max_entries {
+        "selling"
+    } else {
+        "ended"
+    }
+}
+
+// columns padded to their widest cell
+pub fn table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| rows.iter().filter_map(|row| row.get(column)).map(|cell| cell.chars().count()).max().unwrap_or(0))
+        .collect();
+
+    rows.iter()
+        .map(|row| {
+            let line: Vec<String> = row.iter().enumerate().map(|(column, cell)| format!("{:width$}", cell, width = widths[column])).collect();
+            line.join("  ").trim_end().to_string() + "\n"
+        })
+        .collect()
+}
+
+fn row(label: &str, value: impl ToString) -> Vec<String> {
+    vec![label.to_string(), value.to_string()]
+}
+
+pub fn render(address: &Pubkey, tickets_address: &Pubkey, raffle: &RaffleAccount, tickets: &TicketAccount, now: i64) -> String {
+    let max_entries = if raffle.max_entries == u64::MAX { "no cap".to_string() } else { raffle.max_entries.to_string() };
+
+    let mut summary = vec![
+        row("raffle", address),
+        row("status", status(raffle, now)),
+        row("owner", raffle.owner),
+        row("cost mint", raffle.mint),
+        row("prize mint", raffle.prize),
+        row("tickets", tickets_address),
+        row("nonce", raffle.nonce),
+        row("price", format!("{} at {} decimals{}", raffle.price, raffle.cost_decimals, if raffle.native { ", native" } else { "" })),
+        row("prize", format!("{} at {} decimals, {} per winner", raffle.prize_quantity, raffle.prize_decimals, raffle.per_win)),
+        row("window", format!("{} .. {}", raffle.start, raffle.end)),
+        row("sold", format!("{} of {}, {} buyers", raffle.tickets_purchased, max_entries, raffle.unique_entries)),
+        row("min tickets", raffle.min_tickets),
+        row("refunded", raffle.tickets_refunded),
+        row("proceeds fee", format!("{} bps", raffle.fee_bps)),
+        row("winners", format!("{} of {}", raffle.winners.len(), raffle.winner_count())),
+    ];
+
+    if raffle.tiers.len() > 1 {
+        summary.push(row("tiers", raffle.tiers.len()));
+    }
+
+    if !raffle.bundle.is_empty() {
+        summary.push(row("bundle", format!("{} nfts", raffle.bundle.len())));
+    }
+
+    if raffle.locked_at != 0 {
+        summary.push(row("locked at", raffle.locked_at));
+    }
+
+    if raffle.revealed {
+        summary.push(row("seed", to_hex(&raffle.seed)));
+        summary.push(row("draw nonce", raffle.draw_nonce));
+    }
+
+    let mut out = table(&summary);
+
+    if !raffle.winners.is_empty() {
+        let mut winners = vec![["slot", "ticket", "buyer", "prize", "amount", "claimed"].map(String::from).to_vec()];
+
+        for (slot, ticket) in raffle.winners.iter().enumerate() {
+            let prize = raffle.slot_prize(slot);
+
+            winners.push(vec![
+                slot.to_string(),
+                ticket.to_string(),
+                tickets.buyer(*ticket).map_or("unknown".to_string(), |buyer| buyer.to_string()),
+                prize.as_ref().map_or("unknown".to_string(), |prize| prize.mint.to_string()),
+                prize.as_ref().map_or("-".to_string(), |prize| prize.per_win.to_string()),
+                if raffle.claimed[slot] { "yes" } else { "no" }.to_string(),
+            ]);
+        }
+
+        out.push('\n');
+        out.push_str(&table(&winners));
+    }
+
+    out
+}
+
+// the replayed draw, then either a clean bill or every mismatch found
+pub fn render_verification(address: &Pubkey, verification: &Verification) -> String {
+    let mut out = table(&[row("raffle", address), row("seed", to_hex(&verification.seed))]);
+
+    if !verification.winners.is_empty() {
+        let mut winners = vec![["slot", "ticket", "buyer", "draw nonce"].map(String::from).to_vec()];
+
+        for winner in &verification.winners {
+            winners.push(vec![
+                winner.slot.to_string(),
+                winner.ticket.to_string(),
+                winner.buyer.map_or("unknown".to_string(), |buyer| buyer.to_string()),
+                winner.draw_nonce.to_string(),
+            ]);
+        }
+
+        out.push('\n');
+        out.push_str(&table(&winners));
+    }
+
+    out.push('\n');
+
+    if verification.is_valid() {
+        out.push_str("the draw checks out\n");
+    } else {
+        for mismatch in &verification.mismatches {
+            out.push_str(&format!("mismatch: {}\n", mismatch));
+        }
+    }
+
+    out
+}
@@ -0,0 +1,4 @@
+// the offline half of the raffler cli, main.rs puts it in front of an rpc node
+
+pub mod spec;
+pub mod inspect;
@@ -0,0 +1,146 @@
+mod commands;
+
+use {
+    anyhow::{anyhow, bail, Result},
+    clap::{Arg, ArgMatches, Command},
+    commands::Operator,
+    raffler_cli::spec::*,
+    raffler_cli::inspect::render_verification,
+    raffler_client::{accounts::*, verify::*, Purchase},
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{commitment_config::CommitmentConfig, signature::{read_keypair_file, Keypair}},
+    std::{fs, path::Path},
+};
+
+fn raffle_arg() -> Arg<'static> {
+    Arg::new("raffle").required(true).help("Raffle address")
+}
+
+fn cli() -> Command<'static> {
+    Command::new("raffler")
+        .about("Runs raffles on the raffler program")
+        .subcommand_required(true)
+        .arg(Arg::new("url").short('u').long("url").global(true).takes_value(true).default_value("http://127.0.0.1:8899").help("RPC endpoint"))
+        .arg(Arg::new("keypair").short('k').long("keypair").global(true).takes_value(true).help("Wallet keypair, defaults to ~/.config/solana/id.json"))
+        .subcommand(Command::new("create")
+            .about("Create a raffle from a TOML or JSON spec, the wallet is the owner")
+            .arg(Arg::new("spec").required(true)))
+        .subcommand(Command::new("buy")
+            .about("Buy tickets with the wallet")
+            .arg(raffle_arg())
+            .arg(Arg::new("amount").required(true).help("Tickets to buy"))
+            .arg(Arg::new("allocation").long("allocation").takes_value(true).help("Allowlist allocation"))
+            .arg(Arg::new("proof").long("proof").takes_value(true).multiple_occurrences(true).help("Allowlist proof node in hex, in order"))
+            .arg(Arg::new("gate-token").long("gate-token").takes_value(true).help("Token account holding the gate mint or collection nft"))
+            .arg(Arg::new("gate-metadata").long("gate-metadata").takes_value(true).help("Metadata of the gate nft")))
+        .subcommand(Command::new("draw")
+            .about("Lock and reveal or request the oracle's randomness if needed, then crank set_winner until every winner is selected")
+            .arg(raffle_arg())
+            .arg(Arg::new("secret").long("secret").takes_value(true).help("Commit-reveal secret in hex, defaults to <raffle>.secret")))
+        .subcommand(Command::new("pay")
+            .about("Pay every unpaid winner with draw_winner, the wallet has to be the owner")
+            .arg(raffle_arg()))
+        .subcommand(Command::new("close")
+            .about("Close the raffle's open prize tiers and the raffle, and settle proceeds")
+            .arg(raffle_arg())
+            .arg(Arg::new("force").long("force").help("Admin close past the usual checks")))
+        .subcommand(Command::new("show")
+            .about("Print a raffle and its winners")
+            .arg(raffle_arg()))
+        .subcommand(Command::new("verify")
+            .about("Replay set_winner offline and check it against the recorded winners, without an input only the draw from the recorded seed is checked")
+            .arg(raffle_arg())
+            .arg(Arg::new("secret").long("secret").takes_value(true).conflicts_with("oracle-proof").help("Revealed commit-reveal secret in hex"))
+            .arg(Arg::new("oracle-proof").long("oracle-proof").takes_value(true).help("Oracle proof passed to fulfill_randomness, in hex"))
+            .arg(Arg::new("raffle-data").long("raffle-data").takes_value(true).requires("tickets-data").help("Raw raffle account data, instead of fetching it"))
+            .arg(Arg::new("tickets-data").long("tickets-data").takes_value(true).requires("raffle-data").help("Raw ticket account data, instead of fetching it")))
+}
+
+fn rpc(matches: &ArgMatches) -> RpcClient {
+    RpcClient::new_with_commitment(matches.value_of("url").unwrap(), CommitmentConfig::confirmed())
+}
+
+fn operator(matches: &ArgMatches) -> Result<Operator> {
+    let path = match matches.value_of("keypair") {
+        Some(path) => path.to_string(),
+        None => format!("{}/.config/solana/id.json", std::env::var("HOME")?),
+    };
+
+    Ok(Operator {
+        rpc: rpc(matches),
+        payer: read_keypair_file(&path).map_err(|err| anyhow!("reading {}: {}", path, err))?,
+    })
+}
+
+fn purchase(matches: &ArgMatches) -> Result<Purchase> {
+    Ok(Purchase {
+        amount: matches.value_of_t("amount")?,
+        allocation: matches.value_of("allocation").map(str::parse).transpose()?.unwrap_or(0),
+        proof: matches.values_of("proof").into_iter().flatten().map(parse_hex32).collect::<Result<_>>()?,
+        gate_token: matches.value_of("gate-token").map(parse_pubkey).transpose()?,
+        gate_metadata: matches.value_of("gate-metadata").map(parse_pubkey).transpose()?,
+    })
+}
+
+fn randomness_input(matches: &ArgMatches) -> Result<RandomnessInput> {
+    Ok(if let Some(secret) = matches.value_of("secret") {
+        RandomnessInput::Secret(parse_hex32(secret)?)
+    } else if let Some(proof) = matches.value_of("oracle-proof") {
+        RandomnessInput::OracleProof(parse_hex(proof)?)
+    } else {
+        RandomnessInput::Recorded
+    })
+}
+
+// nothing is signed, so this runs without a wallet and fully offline when given the account data
+fn verify(matches: &ArgMatches, args: &ArgMatches) -> Result<()> {
+    let raffle = parse_pubkey(args.value_of("raffle").unwrap())?;
+
+    let (account, tickets) = match (args.value_of("raffle-data"), args.value_of("tickets-data")) {
+        (Some(raffle_data), Some(tickets_data)) => (
+            decode_raffle(&fs::read(raffle_data)?).map_err(|err| anyhow!("{} isn't a raffle: {}", raffle_data, err))?,
+            decode_tickets(&fs::read(tickets_data)?).map_err(|err| anyhow!("{} isn't a ticket account: {}", tickets_data, err))?,
+        ),
+        _ => Operator { rpc: rpc(matches), payer: Keypair::new() }.snapshot(&raffle)?,
+    };
+
+    if account.id != raffle {
+        bail!("the raffle data is for {}, not {}", account.id, raffle);
+    }
+
+    let verification = verify_draw(&account, &tickets, randomness_input(args)?);
+
+    print!("{}", render_verification(&raffle, &verification));
+
+    if !verification.is_valid() {
+        bail!("{} mismatches", verification.mismatches.len());
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let matches = cli().get_matches();
+    let (command, args) = matches.subcommand().unwrap();
+
+    if command == "verify" {
+        return verify(&matches, args);
+    }
+
+    let operator = operator(&matches)?;
+
+    if command == "create" {
+        return operator.create(Path::new(args.value_of("spec").unwrap()));
+    }
+
+    let raffle = parse_pubkey(args.value_of("raffle").unwrap())?;
+
+    match command {
+        "buy" => operator.buy(&raffle, &purchase(args)?),
+        "draw" => operator.draw(&raffle, args.value_of("secret").map(parse_hex32).transpose()?),
+        "pay" => operator.pay(&raffle),
+        "close" => operator.close(&raffle, args.is_present("force")),
+        "show" => operator.show(&raffle),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,183 @@
+use {
+    anchor_lang::prelude::Pubkey,
+    anchor_spl::token::spl_token::native_mint,
+    anyhow::{anyhow, bail, Context, Result},
+    raffler_anchor::{randomness::commitment, state::*},
+    serde::Deserialize,
+    std::{fs, path::Path, str::FromStr},
+};
+
+fn one() -> u64 {
+    1
+}
+
+// what the ops team writes for a raffle, TOML or JSON by file extension
+// price and prize_quantity are whole units at cost_decimals/prize_decimals, those default to the mints' own decimals
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RaffleSpec {
+    // native raffles can leave it out
+    #[serde(default)]
+    pub cost_mint: Option<String>,
+    pub prize_mint: String,
+    // defaults to the current unix time like main.js
+    #[serde(default)]
+    pub nonce: Option<u64>,
+    pub prize_quantity: u64,
+    #[serde(default = "one")]
+    pub per_win: u64,
+    pub price: u64,
+    // defaults to now
+    #[serde(default)]
+    pub start: Option<i64>,
+    pub end: i64,
+    #[serde(default)]
+    pub max_entries: u64,
+    #[serde(default)]
+    pub min_tickets: u64,
+    #[serde(default)]
+    pub max_tickets_per_wallet: u64,
+    #[serde(default)]
+    pub cost_decimals: Option<u8>,
+    #[serde(default)]
+    pub prize_decimals: Option<u8>,
+    #[serde(default)]
+    pub win_multiple: bool,
+    #[serde(default)]
+    pub burn: bool,
+    #[serde(default)]
+    pub fixed: bool,
+    #[serde(default)]
+    pub native: bool,
+    // hex, a fresh one is generated when it's left out
+    #[serde(default)]
+    pub secret: Option<String>,
+    // draws from this oracle instead of commit-reveal
+    #[serde(default)]
+    pub oracle: Option<String>,
+    // hex merkle root
+    #[serde(default)]
+    pub allowlist: Option<String>,
+    #[serde(default)]
+    pub gate: GateSpec,
+    // turns on verify_metadata
+    #[serde(default)]
+    pub prize_metadata: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub nft_uri: String,
+    #[serde(default)]
+    pub nft_image: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GateSpec {
+    #[default]
+    Open,
+    Token { mint: String, amount: u64 },
+    Collection { collection: String },
+}
+
+pub fn parse_pubkey(value: &str) -> Result<Pubkey> {
+    Pubkey::from_str(value).map_err(|_| anyhow!("{} isn't a valid address", value))
+}
+
+pub fn parse_hex32(value: &str) -> Result<[u8; 32]> {
+    parse_hex(value)
+}
+
+pub fn parse_hex<const N: usize>(value: &str) -> Result<[u8; N]> {
+    let value = value.trim().trim_start_matches("0x");
+
+    if value.len() != N * 2 || !value.is_ascii() {
+        bail!("expected {} bytes of hex, got {:?}", N, value);
+    }
+
+    let mut bytes = [0; N];
+
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[index * 2 .. index * 2 + 2], 16).with_context(|| format!("{:?} isn't hex", value))?;
+    }
+
+    Ok(bytes)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl RaffleSpec {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+
+        Self::parse(&text, path.extension().is_some_and(|extension| extension == "json"))
+            .with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn parse(text: &str, json: bool) -> Result<Self> {
+        Ok(if json { serde_json::from_str(text)? } else { toml::from_str(text)? })
+    }
+
+    pub fn mint_cost(&self) -> Result<Pubkey> {
+        match (&self.cost_mint, self.native) {
+            (Some(mint), _) => parse_pubkey(mint),
+            (None, true) => Ok(native_mint::ID),
+            (None, false) => bail!("cost_mint is required unless the raffle is native"),
+        }
+    }
+
+    pub fn mint_prize(&self) -> Result<Pubkey> {
+        parse_pubkey(&self.prize_mint)
+    }
+
+    pub fn secret(&self) -> Result<Option<[u8; 32]>> {
+        self.secret.as_deref().map(parse_hex32).transpose()
+    }
+
+    pub fn prize_metadata(&self) -> Result<Option<Pubkey>> {
+        self.prize_metadata.as_deref().map(parse_pubkey).transpose()
+    }
+
+    // `decimals` are the cost and prize mints' decimals, for whichever the spec leaves out
+    pub fn data(&self, nonce: u64, now: i64, decimals: (u8, u8), secret: &[u8; 32]) -> Result<CreateRaffleData> {
+        let gate = match &self.gate {
+            GateSpec::Open => TicketGate::Open,
+            GateSpec::Token { mint, amount } => TicketGate::Token { mint: parse_pubkey(mint)?, amount: *amount },
+            GateSpec::Collection { collection } => TicketGate::Collection { collection: parse_pubkey(collection)? },
+        };
+
+        let (source, oracle) = match &self.oracle {
+            Some(oracle) => (RandomnessSource::Oracle, parse_pubkey(oracle)?),
+            None => (RandomnessSource::SlotHash, Pubkey::default()),
+        };
+
+        Ok(CreateRaffleData {
+            nonce,
+            prize_quantity: self.prize_quantity,
+            price: self.price,
+            start: self.start.unwrap_or(now),
+            end: self.end,
+            max_entries: self.max_entries,
+            min_tickets: self.min_tickets,
+            max_tickets_per_wallet: self.max_tickets_per_wallet,
+            per_win: self.per_win,
+            cost_decimals: self.cost_decimals.unwrap_or(decimals.0),
+            prize_decimals: self.prize_decimals.unwrap_or(decimals.1),
+            win_multiple: self.win_multiple,
+            burn: self.burn,
+            fixed: self.fixed,
+            native: self.native,
+            commitment: commitment(secret),
+            source,
+            oracle,
+            verify_metadata: self.prize_metadata.is_some(),
+            allowlist: self.allowlist.as_deref().map(parse_hex32).transpose()?,
+            gate,
+            description: self.description.clone(),
+            nft_uri: self.nft_uri.clone(),
+            nft_image: self.nft_image.clone(),
+        })
+    }
+}
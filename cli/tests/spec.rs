@@ -0,0 +1,136 @@
+use {
+    anchor_lang::{prelude::Pubkey, AnchorDeserialize},
+    anchor_spl::token::spl_token::native_mint,
+    raffler_anchor::{randomness::commitment, state::*},
+    raffler_cli::{inspect::*, spec::*},
+    raffler_client::{verify::*, TicketAccount, TicketRun},
+    std::path::Path,
+};
+
+const SECRET: [u8; 32] = [9; 32];
+
+#[test]
+fn example_spec_loads() {
+    let spec = RaffleSpec::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("raffle.example.toml")).unwrap();
+    let data = spec.data(5, 100, (6, 9), &SECRET).unwrap();
+
+    assert_eq!(spec.mint_prize().unwrap(), native_mint::ID);
+    assert_eq!((data.nonce, data.start), (5, 100));
+    assert_eq!((data.prize_quantity, data.per_win, data.price), (10, 2, 1));
+    assert_eq!((data.cost_decimals, data.prize_decimals), (0, 0));
+    assert_eq!(data.commitment, commitment(&SECRET));
+    assert_eq!(data.source, RandomnessSource::SlotHash);
+    assert_eq!(data.gate, TicketGate::Open);
+    assert!(!data.verify_metadata);
+}
+
+#[test]
+fn json_specs_and_defaults() {
+    let prize = Pubkey::new_unique();
+    let gate = Pubkey::new_unique();
+    let oracle = Pubkey::new_unique();
+
+    let json = format!(r#"{{
+        "native": true,
+        "prize_mint": "{}",
+        "prize_quantity": 1,
+        "price": 5,
+        "end": 2000,
+        "oracle": "{}",
+        "allowlist": "{}",
+        "gate": {{ "kind": "token", "mint": "{}", "amount": 3 }}
+    }}"#, prize, oracle, "ab".repeat(32), gate);
+
+    let spec = RaffleSpec::parse(&json, true).unwrap();
+    let data = spec.data(1, 1000, (9, 0), &SECRET).unwrap();
+
+    // native raffles default to the native mint and everything else to the mints' decimals
+    assert_eq!(spec.mint_cost().unwrap(), native_mint::ID);
+    assert_eq!((data.cost_decimals, data.prize_decimals, data.per_win), (9, 0, 1));
+    assert_eq!((data.start, data.end), (1000, 2000));
+    assert_eq!((data.source, data.oracle), (RandomnessSource::Oracle, oracle));
+    assert_eq!(data.allowlist, Some([0xab; 32]));
+    assert_eq!(data.gate, TicketGate::Token { mint: gate, amount: 3 });
+}
+
+#[test]
+fn rejects_bad_specs() {
+    let prize = Pubkey::new_unique();
+
+    // spl raffles have to name their cost mint
+    let spec = RaffleSpec::parse(&format!("prize_mint = \"{}\"\nprize_quantity = 1\nprice = 1\nend = 1", prize), false).unwrap();
+    assert!(spec.mint_cost().is_err());
+
+    // typos aren't silently dropped
+    assert!(RaffleSpec::parse(&format!("prize_mint = \"{}\"\nprize_quantity = 1\nprice = 1\nend = 1\nmax_entires = 5", prize), false).is_err());
+
+    assert!(parse_hex32("abcd").is_err());
+    assert!(parse_hex32(&"zz".repeat(32)).is_err());
+    assert_eq!(parse_hex32(&format!("0x{}", to_hex(&SECRET))).unwrap(), SECRET);
+}
+
+#[test]
+fn tables_line_up() {
+    let rows = vec![
+        vec!["a".to_string(), "long value".to_string()],
+        vec!["longer".to_string(), "b".to_string()],
+    ];
+
+    assert_eq!(table(&rows), "a       long value\nlonger  b\n");
+}
+
+#[test]
+fn renders_winners() {
+    let buyer = Pubkey::new_unique();
+    let prize = Pubkey::new_unique();
+
+    // all zeroes is an empty raffle, strings and vecs included
+    let mut raffle = RaffleAccount::deserialize(&mut &[0; 1024][..]).unwrap();
+
+    raffle.prize = prize;
+    raffle.prize_quantity = 2;
+    raffle.per_win = 1;
+    raffle.tickets_purchased = 4;
+    raffle.max_entries = u64::MAX;
+    raffle.end = 50;
+    raffle.locked_at = 60;
+    raffle.revealed = true;
+    raffle.winners_selected = true;
+    raffle.tiers = vec![PrizeTier { mint: prize, quantity: 2, per_win: 1, decimals: 0, closed: false }];
+    raffle.winners = vec![3, 0];
+    raffle.claimed = vec![true, false];
+
+    let tickets = TicketAccount {
+        raffle: Pubkey::new_unique(),
+        runs: vec![TicketRun { buyer, start: 0, end: 4 }],
+    };
+
+    let out = render(&Pubkey::new_unique(), &Pubkey::new_unique(), &raffle, &tickets, 100);
+
+    assert_eq!(status(&raffle, 100), "winners selected");
+    assert!(out.contains("sold          4 of no cap, 0 buyers"));
+    assert!(out.contains(&format!("1     0       {}  {}  1       no", buyer, prize)));
+    assert!(out.lines().any(|line| line.starts_with("slot  ticket  buyer")));
+}
+
+#[test]
+fn renders_verification() {
+    let buyer = Pubkey::new_unique();
+    let raffle = Pubkey::new_unique();
+
+    let mut verification = Verification {
+        seed: [1; 32],
+        winners: vec![DrawnWinner { slot: 0, ticket: 3, buyer: Some(buyer), draw_nonce: 2 }],
+        mismatches: vec![],
+    };
+
+    let out = render_verification(&raffle, &verification);
+
+    assert!(out.contains(&format!("0     3       {}  2", buyer)));
+    assert!(out.ends_with("the draw checks out\n"));
+
+    verification.mismatches.push(Mismatch::Winner { slot: 0, expected: Some(3), recorded: None });
+
+    assert!(render_verification(&raffle, &verification).ends_with("mismatch: slot 0 should be ticket 3 but the raffle has nothing\n"));
+    assert_eq!(parse_hex::<64>(&"ab".repeat(64)).unwrap(), [0xab; 64]);
+}
@@ -0,0 +1,74 @@
+use {
+    anchor_lang::{error::ErrorCode, prelude::*, AccountDeserialize, Discriminator},
+    raffler_anchor::{state::*, tickets::*},
+};
+
+// where the raffle link sits in a ticket account, for memcmp filters when looking one up by raffle
+pub const TICKETS_RAFFLE_OFFSET: usize = 8;
+
+// any of the program's anchor accounts, discriminator checked
+pub fn decode<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut &data[..])
+}
+
+pub fn decode_raffle(data: &[u8]) -> Result<RaffleAccount> {
+    decode(data)
+}
+
+// one buyer's consecutive tickets, start..end
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TicketRun {
+    pub buyer: Pubkey,
+    pub start: u64,
+    pub end: u64,
+}
+
+// an owned copy of a FixedTicketAccount and the runs in use
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TicketAccount {
+    pub raffle: Pubkey,
+    pub runs: Vec<TicketRun>,
+}
+
+impl TicketAccount {
+    pub fn tickets_purchased(&self) -> u64 {
+        self.runs.last().map_or(0, |run| run.end)
+    }
+
+    pub fn buyer(&self, ticket: u64) -> Option<Pubkey> {
+        let index = self.runs.partition_point(|run| run.end <= ticket);
+        self.runs.get(index).map(|run| run.buyer)
+    }
+
+    pub fn tickets_of(&self, buyer: &Pubkey) -> u64 {
+        self.runs.iter().filter(|run| run.buyer == *buyer).map(|run| run.end - run.start).sum()
+    }
+}
+
+// unlike the program this can't trust the owner check, so the header and run count are checked against the data
+pub fn decode_tickets(data: &[u8]) -> Result<TicketAccount> {
+    if data.len() < RAFFLE_ENTRY_OFFSET {
+        return Err(ErrorCode::AccountDidNotDeserialize.into());
+    }
+
+    if data[..8] != FixedTicketAccount::DISCRIMINATOR {
+        return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+
+    let header: &FixedTicketAccount = bytemuck::from_bytes(&data[8 .. RAFFLE_ENTRY_OFFSET]);
+
+    if data.len() < RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * header.count as usize {
+        return Err(ErrorCode::AccountDidNotDeserialize.into());
+    }
+
+    let runs = ticket_runs(data);
+
+    Ok(TicketAccount {
+        raffle: header.raffle_id,
+        runs: runs.iter().enumerate().map(|(index, run)| TicketRun {
+            buyer: run.buyer,
+            start: range_start(runs, index),
+            end: run.end,
+        }).collect(),
+    })
+}
@@ -0,0 +1,263 @@
+use {
+    anchor_lang::{
+        prelude::*,
+        solana_program::{instruction::Instruction, system_instruction, sysvar::{rent::Rent, slot_hashes}},
+        system_program,
+        InstructionData,
+    },
+    anchor_spl::associated_token,
+    raffler_anchor::{accounts, instruction, state::*, ID},
+    crate::pda::*,
+};
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// the associated token program's create_idempotent, for ATAs init_token_accounts doesn't cover like tier and bundle prizes
+pub fn create_token_account(payer: &Pubkey, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: associated_token::ID,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(token_address(owner, mint, token_program), false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+        data: vec![1],
+    }
+}
+
+// has to go in the same transaction as create_raffle, signed by the ticket keypair
+// only the header is paid for, buy_ticket grows the account from there
+pub fn create_ticket_account(keys: &RaffleKeys) -> Instruction {
+    let lamports = Rent::default().minimum_balance(RAFFLE_ENTRY_OFFSET);
+
+    system_instruction::create_account(&keys.owner, &keys.tickets, lamports, RAFFLE_ENTRY_OFFSET as u64, &ID)
+}
+
+// the owner's prize ATA and the raffle's escrow have to exist, see init_token_accounts
+pub fn create_raffle(keys: &RaffleKeys, data: CreateRaffleData, prize_metadata: Option<Pubkey>) -> Instruction {
+    let accounts = accounts::CreateRaffle {
+        payer: keys.owner,
+        mint_cost: keys.mint_cost,
+        token_prize: keys.token_prize(&keys.owner),
+        mint_prize: keys.mint_prize,
+        raffle: keys.raffle,
+        fixed_raffle: keys.tickets,
+        config: config_address(),
+        system_program: system_program::ID,
+        token_program: keys.prize_token_program,
+        escrow_token_prize: keys.escrow_prize(),
+        vault: keys.vault(),
+        prize_metadata,
+    };
+
+    build(accounts, instruction::CreateRaffle { data })
+}
+
+// creates whichever of the recipient's and the raffle's prize and cost ATAs are missing
+pub fn init_token_accounts(keys: &RaffleKeys, payer: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let accounts = accounts::InitTokenAccounts {
+        payer: *payer,
+        mint_cost: keys.mint_cost,
+        mint_prize: keys.mint_prize,
+        token_prize: keys.token_prize(recipient),
+        token_cost: keys.token_cost(recipient),
+        escrow_token_prize: keys.escrow_prize(),
+        escrow_token_cost: keys.escrow_cost(),
+        associated_token: associated_token::ID,
+        system_program: system_program::ID,
+        token_program: keys.prize_token_program,
+        cost_token_program: keys.cost_token_program,
+        raffle: keys.raffle,
+        recipient: *recipient,
+    };
+
+    build(accounts, instruction::InitTokenAccounts {})
+}
+
+// everything buy_ticket takes besides the accounts, most raffles only need an amount
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Purchase {
+    pub amount: u64,
+    // allowlisted raffles only, the wallet's leaf and its proof
+    pub allocation: u64,
+    pub proof: Vec<[u8; 32]>,
+    // gated raffles only, the buyer's gate token account and for collection gates its nft's metadata
+    pub gate_token: Option<Pubkey>,
+    pub gate_metadata: Option<Pubkey>,
+}
+
+impl Purchase {
+    pub fn new(amount: u64) -> Self {
+        Purchase {
+            amount,
+            ..Purchase::default()
+        }
+    }
+}
+
+pub fn buy_ticket(keys: &RaffleKeys, buyer: &Pubkey, purchase: &Purchase) -> Instruction {
+    let accounts = accounts::BuyTicket {
+        payer: *buyer,
+        mint_cost: keys.mint_cost,
+        token_cost: (!keys.native).then(|| keys.token_cost(buyer)),
+        mint_prize: keys.mint_prize,
+        raffle: keys.raffle,
+        fixed_raffle: keys.tickets,
+        system_program: system_program::ID,
+        token_program: keys.cost_token_program,
+        escrow_token_cost: (!keys.native).then(|| keys.escrow_cost()),
+        config: config_address(),
+        vault: keys.vault(),
+        buyer_entry: entry_address(&keys.raffle, buyer),
+        gate_token: purchase.gate_token,
+        gate_metadata: purchase.gate_metadata,
+    };
+
+    build(accounts, instruction::BuyTicket {
+        amount: purchase.amount,
+        allocation: purchase.allocation,
+        proof: purchase.proof.clone(),
+    })
+}
+
+// anyone can lock once the raffle is over
+pub fn lock_raffle(keys: &RaffleKeys, payer: &Pubkey) -> Instruction {
+    let accounts = accounts::LockRaffle {
+        payer: *payer,
+        raffle: keys.raffle,
+    };
+
+    build(accounts, instruction::LockRaffle {})
+}
+
+// anyone can send it once ENTROPY_DELAY has passed since the lock, the reveal then gets the whole REVEAL_TIMEOUT
+pub fn record_slot_hash(keys: &RaffleKeys, payer: &Pubkey) -> Instruction {
+    let accounts = accounts::RecordSlotHash {
+        payer: *payer,
+        raffle: keys.raffle,
+        slot_hashes: slot_hashes::ID,
+    };
+
+    build(accounts, instruction::RecordSlotHash {})
+}
+
+// has to land within MAX_ENTRIES slots of the lock unless record_slot_hash got there first, see slot_hash_at
+pub fn reveal_secret(keys: &RaffleKeys, secret: [u8; 32]) -> Instruction {
+    let accounts = accounts::RevealSecret {
+        payer: keys.owner,
+        raffle: keys.raffle,
+        slot_hashes: slot_hashes::ID,
+    };
+
+    build(accounts, instruction::RevealSecret { secret })
+}
+
+// anyone can send it, each call draws at most one winner or sends a raffle past its reveal timeout to refunds
+// oracle raffles only, once locked, the oracle answers it with fulfill_randomness
+pub fn request_randomness(keys: &RaffleKeys, payer: &Pubkey) -> Instruction {
+    let accounts = accounts::RequestRandomness {
+        payer: *payer,
+        raffle: keys.raffle,
+        request: randomness_address(&keys.raffle),
+        system_program: system_program::ID,
+    };
+
+    build(accounts, instruction::RequestRandomness {})
+}
+
+pub fn set_winner(keys: &RaffleKeys, payer: &Pubkey) -> Instruction {
+    let accounts = accounts::SetWinner {
+        payer: *payer,
+        mint_cost: keys.mint_cost,
+        mint_prize: keys.mint_prize,
+        raffle: keys.raffle,
+        fixed_raffle: keys.tickets,
+        randomness_request: randomness_address(&keys.raffle),
+        slot_hashes: slot_hashes::ID,
+    };
+
+    build(accounts, instruction::SetWinner {})
+}
+
+// pays the last unpaid winner, `mint_prize` is the mint of that winner's tier and `recipient` the ticket's buyer
+// the recipient's ATA has to exist, see init_token_accounts
+pub fn draw_winner(keys: &RaffleKeys, recipient: &Pubkey, mint_prize: &Pubkey, token_program: &Pubkey) -> Instruction {
+    let accounts = accounts::DrawWinner {
+        payer: keys.owner,
+        recipient: *recipient,
+        mint_cost: keys.mint_cost,
+        mint_prize: *mint_prize,
+        raffle: keys.raffle,
+        token_prize: token_address(recipient, mint_prize, token_program),
+        escrow_token_prize: token_address(&keys.raffle, mint_prize, token_program),
+        token_program: *token_program,
+        fixed_raffle: keys.tickets,
+    };
+
+    build(accounts, instruction::DrawWinner {})
+}
+
+// returns what's left of extra tier `tier` to the owner, `mint_prize` is the tier's mint
+// `payer` is the owner, or the admin with `force_close`, the owner's ATA for the mint has to exist
+pub fn close_prize_tier(keys: &RaffleKeys, payer: &Pubkey, fee_recipient: &Pubkey, tier: u8, mint_prize: &Pubkey, token_program: &Pubkey, force_close: bool) -> Instruction {
+    let accounts = accounts::ClosePrizeTier {
+        payer: *payer,
+        raffle: keys.raffle,
+        mint_prize: *mint_prize,
+        token_prize: token_address(&keys.owner, mint_prize, token_program),
+        escrow_token_prize: token_address(&keys.raffle, mint_prize, token_program),
+        fee_recipient: *fee_recipient,
+        config: config_address(),
+        token_program: *token_program,
+    };
+
+    build(accounts, instruction::ClosePrizeTier { tier, force_close })
+}
+
+// `payer` is the owner, or the admin with `force_close`
+// `bundle` is the raffle's bundle in order, each nft adds its [mint, owner token account, raffle escrow] like add_bundle
+pub fn close_raffle(keys: &RaffleKeys, payer: &Pubkey, fee_recipient: &Pubkey, force_close: bool, bundle: &[Pubkey]) -> Instruction {
+    let spl = !keys.native;
+
+    let accounts = accounts::CloseRaffle {
+        payer: *payer,
+        mint_cost: keys.mint_cost,
+        token_prize: keys.token_prize(&keys.owner),
+        token_cost: spl.then(|| keys.token_cost(&keys.owner)),
+        mint_prize: keys.mint_prize,
+        raffle: keys.raffle,
+        owner: keys.owner,
+        fixed_raffle: keys.tickets,
+        system_program: system_program::ID,
+        token_program: keys.prize_token_program,
+        cost_token_program: keys.cost_token_program,
+        escrow_token_prize: keys.escrow_prize(),
+        escrow_token_cost: spl.then(|| keys.escrow_cost()),
+        fee_recipient: *fee_recipient,
+        fee_token_cost: spl.then(|| keys.token_cost(fee_recipient)),
+        config: config_address(),
+        vault: keys.vault(),
+    };
+
+    let mut close = build(accounts, instruction::CloseRaffle { force_close });
+
+    for mint in bundle {
+        // the mint is writable in case there are withheld transfer fees to harvest
+        close.accounts.extend([
+            AccountMeta::new(*mint, false),
+            AccountMeta::new(token_address(&keys.owner, mint, &keys.prize_token_program), false),
+            AccountMeta::new(token_address(&keys.raffle, mint, &keys.prize_token_program), false),
+        ]);
+    }
+
+    close
+}
@@ -0,0 +1,15 @@
+// off-chain helpers for the raffler program: addresses, instruction builders and account decoders
+// the layouts and instruction data come from the program crate itself, so they can't drift from what's deployed
+
+pub mod pda;
+pub mod instructions;
+pub mod accounts;
+pub mod verify;
+
+pub use {
+    raffler_anchor::{self as program, ID},
+    pda::RaffleKeys,
+    instructions::Purchase,
+    accounts::{TicketAccount, TicketRun},
+    verify::{verify_draw, RandomnessInput, Verification},
+};
@@ -0,0 +1,120 @@
+use {
+    anchor_lang::prelude::Pubkey,
+    anchor_spl::{associated_token::get_associated_token_address_with_program_id, token},
+    raffler_anchor::{state::*, ID},
+    solana_sdk::{signature::{keypair_from_seed, Keypair}, signer::Signer},
+};
+
+pub const RANDOMNESS_SEED: &[u8] = b"randomness";
+
+pub fn config_address() -> Pubkey {
+    Pubkey::find_program_address(&[CONFIG_SEED], &ID).0
+}
+
+pub fn raffle_address(owner: &Pubkey, mint_cost: &Pubkey, mint_prize: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), mint_cost.as_ref(), mint_prize.as_ref(), &nonce.to_le_bytes()], &ID).0
+}
+
+pub fn vault_address(raffle: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[VAULT_SEED, raffle.as_ref()], &ID).0
+}
+
+pub fn entry_address(raffle: &Pubkey, buyer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[ENTRY_SEED, raffle.as_ref(), buyer.as_ref()], &ID).0
+}
+
+pub fn randomness_address(raffle: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[RANDOMNESS_SEED, raffle.as_ref()], &ID).0
+}
+
+// associated token account under either token program
+pub fn token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+// the ticket account is a plain keypair account, main.js seeds it with the raffle address so it never has to be stored
+// anyone can derive it, it only matters as a signer for the create_account in front of create_raffle
+pub fn ticket_keypair(raffle: &Pubkey) -> Keypair {
+    keypair_from_seed(raffle.as_ref()).unwrap()
+}
+
+// every address the instructions on one raffle need
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RaffleKeys {
+    pub raffle: Pubkey,
+    pub owner: Pubkey,
+    pub mint_cost: Pubkey,
+    pub mint_prize: Pubkey,
+    pub tickets: Pubkey,
+    pub cost_token_program: Pubkey,
+    pub prize_token_program: Pubkey,
+    pub native: bool,
+}
+
+impl RaffleKeys {
+    // a raffle about to be created, both mints under the original token program and tickets at the main.js address
+    pub fn new(owner: &Pubkey, mint_cost: &Pubkey, mint_prize: &Pubkey, nonce: u64, native: bool) -> Self {
+        let raffle = raffle_address(owner, mint_cost, mint_prize, nonce);
+
+        RaffleKeys {
+            raffle,
+            owner: *owner,
+            mint_cost: *mint_cost,
+            mint_prize: *mint_prize,
+            tickets: ticket_keypair(&raffle).pubkey(),
+            cost_token_program: token::ID,
+            prize_token_program: token::ID,
+            native,
+        }
+    }
+
+    // an existing raffle, it doesn't record its ticket account or token programs so those come from the caller
+    pub fn from_account(raffle: &Pubkey, account: &RaffleAccount, tickets: &Pubkey) -> Self {
+        RaffleKeys {
+            raffle: *raffle,
+            owner: account.owner,
+            mint_cost: account.mint,
+            mint_prize: account.prize,
+            tickets: *tickets,
+            cost_token_program: token::ID,
+            prize_token_program: token::ID,
+            native: account.native,
+        }
+    }
+
+    // for Token-2022 mints, the owner of each mint account
+    pub fn with_token_programs(self, cost_token_program: &Pubkey, prize_token_program: &Pubkey) -> Self {
+        RaffleKeys {
+            cost_token_program: *cost_token_program,
+            prize_token_program: *prize_token_program,
+            ..self
+        }
+    }
+
+    pub fn with_tickets(self, tickets: &Pubkey) -> Self {
+        RaffleKeys {
+            tickets: *tickets,
+            ..self
+        }
+    }
+
+    pub fn escrow_prize(&self) -> Pubkey {
+        token_address(&self.raffle, &self.mint_prize, &self.prize_token_program)
+    }
+
+    pub fn escrow_cost(&self) -> Pubkey {
+        token_address(&self.raffle, &self.mint_cost, &self.cost_token_program)
+    }
+
+    pub fn token_prize(&self, owner: &Pubkey) -> Pubkey {
+        token_address(owner, &self.mint_prize, &self.prize_token_program)
+    }
+
+    pub fn token_cost(&self, owner: &Pubkey) -> Pubkey {
+        token_address(owner, &self.mint_cost, &self.cost_token_program)
+    }
+
+    pub fn vault(&self) -> Option<Pubkey> {
+        self.native.then(|| vault_address(&self.raffle))
+    }
+}
@@ -0,0 +1,167 @@
+use {
+    anchor_lang::prelude::Pubkey,
+    raffler_anchor::{randomness::*, state::*},
+    solana_sdk::signature::Signature,
+    std::fmt,
+    crate::accounts::TicketAccount,
+};
+
+// how far a replay goes looking for winners, only a raffle with fewer distinct tickets than winners gets near it
+pub const MAX_REPLAY_NONCES: u64 = 1 << 20;
+
+// what the draw was seeded from, as published once the raffle is drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RandomnessInput {
+    // commit-reveal, the secret the creator passed to reveal_secret
+    Secret([u8; 32]),
+    // oracle raffles, the proof passed to fulfill_randomness
+    OracleProof([u8; 64]),
+    // the seed as stored on the raffle, only the draw itself gets checked
+    Recorded,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrawnWinner {
+    pub slot: usize,
+    pub ticket: u64,
+    // None if the snapshot doesn't cover the ticket
+    pub buyer: Option<Pubkey>,
+    // the nonce of the draw that picked it, as in WinnerSelected
+    pub draw_nonce: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    // the secret doesn't hash to the raffle's commitment
+    Commitment,
+    // the proof isn't the raffle's oracle signing the request
+    OracleProof,
+    Seed { expected: [u8; 32], recorded: [u8; 32] },
+    // the snapshot links to another raffle
+    TicketAccount { raffle: Pubkey },
+    TicketsPurchased { snapshot: u64, recorded: u64 },
+    Winner { slot: usize, expected: Option<u64>, recorded: Option<u64> },
+    DrawNonce { expected: u64, recorded: u64 },
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ticket = |ticket: &Option<u64>| ticket.map_or("nothing".to_string(), |ticket| format!("ticket {}", ticket));
+
+        match self {
+            Mismatch::Commitment => write!(f, "the secret doesn't match the raffle's commitment"),
+            Mismatch::OracleProof => write!(f, "the proof isn't the oracle's signature over the request"),
+            Mismatch::Seed { expected, recorded } => write!(f, "seed should be {} but the raffle has {}", hex(expected), hex(recorded)),
+            Mismatch::TicketAccount { raffle } => write!(f, "the ticket snapshot belongs to raffle {}", raffle),
+            Mismatch::TicketsPurchased { snapshot, recorded } => write!(f, "the ticket snapshot has {} tickets but the raffle sold {}", snapshot, recorded),
+            Mismatch::Winner { slot, expected, recorded } => write!(f, "slot {} should be {} but the raffle has {}", slot, ticket(expected), ticket(recorded)),
+            Mismatch::DrawNonce { expected, recorded } => write!(f, "draw nonce should be {} but the raffle has {}", expected, recorded),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verification {
+    pub seed: [u8; 32],
+    pub winners: Vec<DrawnWinner>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+// set_winner's draws one nonce at a time, how they're split across calls doesn't change the result
+// returns (ticket, nonce) for every winner and the nonce the next draw would use
+pub fn replay_draws(seed: &[u8; 32], tickets: u64, count: usize, win_multiple: bool) -> (Vec<(u64, u64)>, u64) {
+    let mut winners: Vec<(u64, u64)> = vec![];
+    let mut nonce = 0;
+
+    while winners.len() < count && nonce < MAX_REPLAY_NONCES {
+        let index = winning_index(seed, nonce, tickets);
+        nonce += 1;
+
+        if let Some(index) = index {
+            if !win_multiple && winners.iter().any(|(ticket, _)| *ticket == index) {
+                continue;
+            }
+
+            winners.push((index, nonce - 1));
+        }
+    }
+
+    (winners, nonce)
+}
+
+// reproduces set_winner from the randomness input and a snapshot of the raffle's ticket account
+// the slot hash is taken as recorded, SlotHashes doesn't keep it around long enough to check later
+pub fn verify_draw(raffle: &RaffleAccount, tickets: &TicketAccount, input: RandomnessInput) -> Verification {
+    let mut mismatches = vec![];
+
+    let seed = match input {
+        RandomnessInput::Secret(secret) => {
+            if commitment(&secret) != raffle.commitment {
+                mismatches.push(Mismatch::Commitment);
+            }
+
+            reveal_seed(&secret, &raffle.slot_hash)
+        }
+        RandomnessInput::OracleProof(proof) => {
+            let alpha = oracle_alpha(&raffle.id, raffle.lock_slot);
+
+            if !Signature::from(proof).verify(raffle.oracle.as_ref(), &alpha) {
+                mismatches.push(Mismatch::OracleProof);
+            }
+
+            oracle_seed(&oracle_output(&proof), &raffle.slot_hash)
+        }
+        RandomnessInput::Recorded => raffle.seed,
+    };
+
+    if seed != raffle.seed {
+        mismatches.push(Mismatch::Seed { expected: seed, recorded: raffle.seed });
+    }
+
+    if tickets.raffle != raffle.id {
+        mismatches.push(Mismatch::TicketAccount { raffle: tickets.raffle });
+    }
+
+    if tickets.tickets_purchased() != raffle.tickets_purchased {
+        mismatches.push(Mismatch::TicketsPurchased { snapshot: tickets.tickets_purchased(), recorded: raffle.tickets_purchased });
+    }
+
+    // a draw still in progress is checked as far as it got
+    let count = if raffle.winners_selected { raffle.winner_count() } else { raffle.winners.len() };
+    let (drawn, next_nonce) = replay_draws(&seed, raffle.tickets_purchased, count, raffle.win_multiple);
+
+    for slot in 0..drawn.len().max(raffle.winners.len()) {
+        let expected = drawn.get(slot).map(|(ticket, _)| *ticket);
+        let recorded = raffle.winners.get(slot).copied();
+
+        if expected != recorded {
+            mismatches.push(Mismatch::Winner { slot, expected, recorded });
+        }
+    }
+
+    // empty set_winner calls move the nonce too, so it only has to line up once the last winner is in
+    if raffle.winners_selected && next_nonce != raffle.draw_nonce {
+        mismatches.push(Mismatch::DrawNonce { expected: next_nonce, recorded: raffle.draw_nonce });
+    }
+
+    Verification {
+        seed,
+        winners: drawn.iter().enumerate().map(|(slot, (ticket, draw_nonce))| DrawnWinner {
+            slot,
+            ticket: *ticket,
+            buyer: tickets.buyer(*ticket),
+            draw_nonce: *draw_nonce,
+        }).collect(),
+        mismatches,
+    }
+}
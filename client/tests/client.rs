@@ -0,0 +1,113 @@
+use {
+    anchor_lang::{prelude::*, Discriminator},
+    anchor_spl::token_2022,
+    raffler_anchor::state::*,
+    raffler_client::{accounts::*, instructions::*, pda::*, RaffleKeys, ID},
+};
+
+fn ticket_data(raffle: &Pubkey, runs: &[(Pubkey, u64)]) -> Vec<u8> {
+    let mut data = FixedTicketAccount::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(raffle.as_ref());
+    data.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+
+    for (buyer, end) in runs {
+        data.extend_from_slice(buyer.as_ref());
+        data.extend_from_slice(&end.to_le_bytes());
+    }
+
+    data
+}
+
+#[test]
+fn decodes_ticket_runs() {
+    let raffle = Pubkey::new_unique();
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = ticket_data(&raffle, &[(a, 2), (b, 5), (a, 6)]);
+
+    // room for a run that isn't in use yet
+    data.extend_from_slice(&[0; RAFFLE_ENTRY_SIZE]);
+
+    let tickets = decode_tickets(&data).unwrap();
+
+    assert_eq!(tickets.raffle, raffle);
+    assert_eq!(tickets.runs, vec![
+        TicketRun { buyer: a, start: 0, end: 2 },
+        TicketRun { buyer: b, start: 2, end: 5 },
+        TicketRun { buyer: a, start: 5, end: 6 },
+    ]);
+    assert_eq!(tickets.tickets_purchased(), 6);
+    assert_eq!(tickets.tickets_of(&a), 3);
+    assert_eq!((tickets.buyer(1), tickets.buyer(2), tickets.buyer(5), tickets.buyer(6)), (Some(a), Some(b), Some(a), None));
+}
+
+#[test]
+fn rejects_bad_ticket_data() {
+    let data = ticket_data(&Pubkey::new_unique(), &[(Pubkey::new_unique(), 1)]);
+
+    let mut wrong_type = data.clone();
+    wrong_type[0] ^= 1;
+    assert!(decode_tickets(&wrong_type).is_err());
+
+    // a count past the end of the data
+    assert!(decode_tickets(&data[..data.len() - 1]).is_err());
+    assert!(decode_tickets(&data[..RAFFLE_ENTRY_OFFSET - 1]).is_err());
+}
+
+#[test]
+fn native_and_spl_raffles_pass_different_accounts() {
+    let owner = Pubkey::new_unique();
+    let buyer = Pubkey::new_unique();
+    let prize = Pubkey::new_unique();
+
+    let native = RaffleKeys::new(&owner, &anchor_spl::token::spl_token::native_mint::ID, &prize, 0, true);
+    let spl = RaffleKeys::new(&owner, &Pubkey::new_unique(), &prize, 0, false);
+
+    let keys = |instruction: anchor_lang::solana_program::instruction::Instruction| -> Vec<Pubkey> {
+        instruction.accounts.iter().map(|meta| meta.pubkey).collect()
+    };
+
+    let native_buy = keys(buy_ticket(&native, &buyer, &Purchase::new(1)));
+    let spl_buy = keys(buy_ticket(&spl, &buyer, &Purchase::new(1)));
+
+    assert!(native_buy.contains(&vault_address(&native.raffle)));
+    assert!(!native_buy.contains(&native.token_cost(&buyer)));
+    assert!(spl_buy.contains(&spl.token_cost(&buyer)) && spl_buy.contains(&spl.escrow_cost()));
+    assert!(!spl_buy.contains(&vault_address(&spl.raffle)));
+
+    // missing optional accounts are passed as the program id
+    assert!(spl_buy.contains(&ID));
+    assert!(native_buy.contains(&entry_address(&native.raffle, &buyer)));
+}
+
+#[test]
+fn token_programs_move_the_atas() {
+    let keys = RaffleKeys::new(&Pubkey::new_unique(), &Pubkey::new_unique(), &Pubkey::new_unique(), 7, false);
+    let token_2022 = keys.clone().with_token_programs(&token_2022::ID, &token_2022::ID);
+
+    assert_eq!(keys.raffle, token_2022.raffle);
+    assert_ne!(keys.escrow_prize(), token_2022.escrow_prize());
+    assert_eq!(token_2022.escrow_prize(), token_address(&keys.raffle, &keys.mint_prize, &token_2022::ID));
+
+    let close = close_raffle(&token_2022, &keys.owner, &Pubkey::new_unique(), false, &[Pubkey::new_unique(), Pubkey::new_unique()]);
+    let bare = close_raffle(&token_2022, &keys.owner, &Pubkey::new_unique(), false, &[]);
+
+    assert_eq!(close.accounts.len(), bare.accounts.len() + 6);
+}
+
+#[test]
+fn tiers_close_through_their_own_token_program() {
+    let keys = RaffleKeys::new(&Pubkey::new_unique(), &Pubkey::new_unique(), &Pubkey::new_unique(), 0, false);
+    let admin = Pubkey::new_unique();
+    let tier_mint = Pubkey::new_unique();
+
+    let close = close_prize_tier(&keys, &admin, &Pubkey::new_unique(), 1, &tier_mint, &token_2022::ID, true);
+    let accounts: Vec<Pubkey> = close.accounts.iter().map(|meta| meta.pubkey).collect();
+
+    // the leftover goes to the owner even when the admin closes
+    assert!(accounts.contains(&token_address(&keys.owner, &tier_mint, &token_2022::ID)));
+    assert!(accounts.contains(&token_address(&keys.raffle, &tier_mint, &token_2022::ID)));
+    assert!(!accounts.contains(&token_address(&admin, &tier_mint, &token_2022::ID)));
+
+    let request = request_randomness(&keys, &admin);
+    assert!(request.accounts.iter().any(|meta| meta.pubkey == randomness_address(&keys.raffle) && meta.is_writable));
+}
@@ -0,0 +1,148 @@
+use {
+    anchor_lang::{prelude::Pubkey, AnchorDeserialize},
+    raffler_anchor::{randomness::*, state::*},
+    raffler_client::{verify::*, TicketAccount, TicketRun},
+    solana_sdk::{signature::Keypair, signer::Signer},
+};
+
+const SECRET: [u8; 32] = [7; 32];
+const SLOT_HASH: [u8; 32] = [3; 32];
+
+// set_winner as the program runs it, a few attempts per call until every slot is filled
+fn run_set_winner(raffle: &mut RaffleAccount) {
+    while raffle.winners.len() < raffle.winner_count() {
+        for _ in 0..MAX_DRAW_ATTEMPTS {
+            let index = winning_index(&raffle.seed, raffle.draw_nonce, raffle.tickets_purchased);
+            raffle.draw_nonce += 1;
+
+            if let Some(index) = index {
+                if raffle.winners.contains(&index) && !raffle.win_multiple {
+                    continue;
+                }
+
+                raffle.winners.push(index);
+                break;
+            }
+        }
+    }
+
+    raffle.winners_selected = true;
+}
+
+fn drawn_raffle(seed: [u8; 32], winners: u64) -> (RaffleAccount, TicketAccount) {
+    let prize = Pubkey::new_unique();
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    // all zeroes is an empty raffle, strings and vecs included
+    let mut raffle = RaffleAccount::deserialize(&mut &[0; 1024][..]).unwrap();
+
+    raffle.id = Pubkey::new_unique();
+    raffle.prize = prize;
+    raffle.prize_quantity = winners;
+    raffle.per_win = 1;
+    raffle.tiers = vec![PrizeTier { mint: prize, quantity: winners, per_win: 1, decimals: 0, closed: false }];
+    raffle.tickets_purchased = 10;
+    raffle.locked_at = 1;
+    raffle.lock_slot = 10;
+    raffle.commitment = commitment(&SECRET);
+    raffle.slot_hash = SLOT_HASH;
+    raffle.seed = seed;
+    raffle.revealed = true;
+
+    run_set_winner(&mut raffle);
+
+    let tickets = TicketAccount {
+        raffle: raffle.id,
+        runs: vec![TicketRun { buyer: a, start: 0, end: 4 }, TicketRun { buyer: b, start: 4, end: 10 }],
+    };
+
+    (raffle, tickets)
+}
+
+#[test]
+fn reproduces_a_commit_reveal_draw() {
+    let (raffle, tickets) = drawn_raffle(reveal_seed(&SECRET, &SLOT_HASH), 4);
+    let verification = verify_draw(&raffle, &tickets, RandomnessInput::Secret(SECRET));
+
+    assert!(verification.is_valid(), "{:?}", verification.mismatches);
+    assert_eq!(verification.winners.iter().map(|winner| winner.ticket).collect::<Vec<_>>(), raffle.winners);
+
+    for winner in &verification.winners {
+        assert_eq!(winner.buyer, tickets.buyer(winner.ticket));
+        assert_eq!(winning_index(&raffle.seed, winner.draw_nonce, 10), Some(winner.ticket));
+    }
+
+    // without win_multiple every winner is a different ticket
+    let mut distinct = raffle.winners.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), 4);
+}
+
+#[test]
+fn flags_a_wrong_secret() {
+    let (raffle, tickets) = drawn_raffle(reveal_seed(&SECRET, &SLOT_HASH), 2);
+    let verification = verify_draw(&raffle, &tickets, RandomnessInput::Secret([8; 32]));
+
+    assert!(verification.mismatches.contains(&Mismatch::Commitment));
+    assert!(verification.mismatches.iter().any(|mismatch| matches!(mismatch, Mismatch::Seed { .. })));
+}
+
+#[test]
+fn flags_tampered_winners() {
+    let (mut raffle, tickets) = drawn_raffle(reveal_seed(&SECRET, &SLOT_HASH), 3);
+
+    // hand the first slot to a ticket from the other buyer
+    let original = raffle.winners[0];
+    let swapped = if original < 4 { 9 } else { 0 };
+    raffle.winners[0] = swapped;
+
+    let verification = verify_draw(&raffle, &tickets, RandomnessInput::Recorded);
+
+    assert!(verification.mismatches.contains(&Mismatch::Winner { slot: 0, expected: Some(original), recorded: Some(swapped) }));
+
+    // a snapshot that doesn't add up is called out as well
+    let mut short = tickets.clone();
+    short.runs.pop();
+    let verification = verify_draw(&raffle, &short, RandomnessInput::Recorded);
+
+    assert!(verification.mismatches.contains(&Mismatch::TicketsPurchased { snapshot: 4, recorded: 10 }));
+}
+
+#[test]
+fn checks_oracle_proofs() {
+    let oracle = Keypair::new();
+    let (mut raffle, tickets) = drawn_raffle([0; 32], 2);
+
+    raffle.oracle = oracle.pubkey();
+
+    let proof: [u8; 64] = oracle.sign_message(&oracle_alpha(&raffle.id, raffle.lock_slot)).into();
+    raffle.seed = oracle_seed(&oracle_output(&proof), &SLOT_HASH);
+    raffle.winners.clear();
+    raffle.draw_nonce = 0;
+    run_set_winner(&mut raffle);
+
+    assert!(verify_draw(&raffle, &tickets, RandomnessInput::OracleProof(proof)).is_valid());
+
+    // anyone else's signature doesn't count, even over the right request
+    let forged: [u8; 64] = Keypair::new().sign_message(&oracle_alpha(&raffle.id, raffle.lock_slot)).into();
+    assert!(verify_draw(&raffle, &tickets, RandomnessInput::OracleProof(forged)).mismatches.contains(&Mismatch::OracleProof));
+}
+
+#[test]
+fn checks_a_draw_in_progress_and_the_nonce() {
+    let (mut raffle, tickets) = drawn_raffle(reveal_seed(&SECRET, &SLOT_HASH), 3);
+
+    let mut partial = raffle.clone();
+    partial.winners.truncate(1);
+    partial.winners_selected = false;
+
+    assert!(verify_draw(&partial, &tickets, RandomnessInput::Secret(SECRET)).is_valid());
+
+    raffle.draw_nonce += 1;
+
+    let expected = raffle.draw_nonce - 1;
+    assert!(verify_draw(&raffle, &tickets, RandomnessInput::Recorded)
+        .mismatches
+        .contains(&Mismatch::DrawNonce { expected, recorded: raffle.draw_nonce }));
+}
@@ -1,5 +1,6 @@
 (async () => {
-  const anchor = require('@project-serum/anchor'),
+  const anchor = require('@coral-xyz/anchor'),
+    crypto = require('crypto'),
     fs = require('fs');
   require('dotenv').config();
 
@@ -10,11 +11,7 @@
     mintPrize = new anchor.web3.PublicKey(process.env.mint_prize),
     systemProgram = new anchor.web3.PublicKey(
       '11111111111111111111111111111111',
-    ),
-    rent = new anchor.web3.PublicKey(
-      'SysvarRent111111111111111111111111111111111',
-    ),
-    spl_token_program = anchor.Spl.token();
+    );
 
   anchor.setProvider(anchor.AnchorProvider.local(process.env.rpc));
 
@@ -43,11 +40,19 @@
       anchor.AnchorProvider.defaultOptions(),
     );
 
+  const [config] = await anchor.web3.PublicKey.findProgramAddress(
+    [Buffer.from('config')],
+    programId,
+  );
+
+  const nonce = new anchor.BN(Date.now());
+
   const [raffle, bump] = await anchor.web3.PublicKey.findProgramAddress(
       [
         payer.wallet.publicKey.toBytes(),
         mintCost.toBytes(),
         mintPrize.toBytes(),
+        nonce.toArrayLike(Buffer, 'le', 8),
       ],
       programId,
     ),
@@ -79,6 +84,10 @@
       owner: buyer.publicKey,
     });
 
+  // the creator commits to this at create and reveals it once the raffle is locked
+  const secret = crypto.randomBytes(32),
+    commitment = crypto.createHash('sha256').update(secret).digest();
+
   anchor.setProvider(buyer_provider);
   const buyer_program = new anchor.Program(raffler_idl, programId);
 
@@ -102,9 +111,9 @@
           escrowTokenCost,
           associatedToken: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
           tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+          costTokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
           systemProgram,
           raffle,
-          rent,
           recipient: payer.wallet.publicKey,
         },
       };
@@ -113,6 +122,7 @@
     }
 
     const args = {
+      nonce,
       prizeQuantity: new anchor.BN(5),
       price: new anchor.BN(1),
       start: new anchor.BN(150),
@@ -120,10 +130,19 @@
       costDecimals: 9,
       prizeDecimals: 9,
       maxEntries: new anchor.BN(99999),
+      minTickets: new anchor.BN(0),
+      maxTicketsPerWallet: new anchor.BN(0),
       perWin: new anchor.BN(1),
       winMultiple: true,
       burn: false,
       fixed: true,
+      native: false,
+      commitment: [...commitment],
+      source: { slotHash: {} },
+      oracle: systemProgram,
+      verifyMetadata: false,
+      allowlist: null,
+      gate: { open: {} },
       description: 'AAAAAAAAAAAAAAAAAAA',
       nftUri: 'AAAAAAAAAAAAAAAAAAA',
       nftImage: 'AAAAAAAAAAAAAAAAAAA',
@@ -140,6 +159,9 @@
         tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         escrowTokenPrize,
         fixedRaffle: fixedRaffle.publicKey,
+        config,
+        vault: null,
+        prizeMetadata: null,
       },
     };
 
@@ -148,10 +170,11 @@
         fromPubkey: payer.wallet.publicKey,
         newAccountPubkey: fixedRaffle.publicKey,
         programId: programId,
+        // just the header, buy_ticket grows it
         lamports: await payer.connection.getMinimumBalanceForRentExemption(
-          8 + 32 + 4 + 35 * args.maxEntries,
+          8 + 32 + 4,
         ),
-        space: 8 + 32 + 4 + 35 * args.maxEntries,
+        space: 8 + 32 + 4,
       }),
     );
     tx.add(await program.instruction.createRaffle(args, ctx));
@@ -162,6 +185,14 @@
   }
 
   async function testCloseRaffle(force_close) {
+    const { feeRecipient } = await program.account.protocolConfig.fetch(
+        config,
+      ),
+      feeTokenCost = await anchor.utils.token.associatedAddress({
+        mint: mintCost,
+        owner: feeRecipient,
+      });
+
     const ctx = {
       accounts: {
         payer: payer.wallet.publicKey,
@@ -170,14 +201,17 @@
         tokenCost,
         mintPrize,
         raffle,
+        owner: payer.wallet.publicKey,
         systemProgram,
         tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        costTokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         escrowTokenPrize,
         escrowTokenCost,
         fixedRaffle: fixedRaffle.publicKey,
-        moon: new anchor.web3.PublicKey(
-          'MoonJpLwzBSu2SEeXe42rDySA21NifCuPBDPr5jExET',
-        ),
+        feeRecipient,
+        feeTokenCost,
+        config,
+        vault: null,
       },
     };
 
@@ -190,6 +224,11 @@
       owner: buyer.publicKey,
     });
 
+    const [buyerEntry] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from('entry'), raffle.toBytes(), buyer.publicKey.toBytes()],
+      programId,
+    );
+
     const ctx = {
       accounts: {
         payer: buyer.publicKey,
@@ -201,17 +240,53 @@
         tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         escrowTokenCost,
         fixedRaffle: fixedRaffle.publicKey,
+        config,
+        vault: null,
+        buyerEntry,
+        gateToken: null,
+        gateMetadata: null,
       },
     };
 
-    return await buyer_program.rpc.buyTicket(new anchor.BN(1), ctx);
+    return await buyer_program.rpc.buyTicket(
+      new anchor.BN(1),
+      new anchor.BN(0),
+      [],
+      ctx,
+    );
   }
 
-  async function testPickWinner() {
+  async function testLockRaffle() {
     const slotHashes = new anchor.web3.PublicKey(
       'SysvarS1otHashes111111111111111111111111111',
     );
 
+    await program.rpc.lockRaffle({
+      accounts: {
+        payer: payer.wallet.publicKey,
+        raffle,
+      },
+    });
+
+    // the reveal mixes in the hash of a slot a few past the lock
+    await new Promise((resolve) => setTimeout(resolve, 4000));
+
+    return await program.rpc.revealSecret([...secret], {
+      accounts: {
+        payer: payer.wallet.publicKey,
+        raffle,
+        slotHashes,
+      },
+    });
+  }
+
+  async function testPickWinner() {
+    const [randomnessRequest] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from('randomness'), raffle.toBytes()],
+        programId,
+      );
+
     const ctx = {
       accounts: {
         payer: payer.wallet.publicKey,
@@ -219,7 +294,10 @@
         mintPrize,
         raffle,
         fixedRaffle: fixedRaffle.publicKey,
-        slotHashes,
+        randomnessRequest,
+        slotHashes: new anchor.web3.PublicKey(
+          'SysvarS1otHashes111111111111111111111111111',
+        ),
       },
     };
 
@@ -248,7 +326,7 @@
           associatedToken: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
           systemProgram,
           tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
-          rent,
+          costTokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
           raffle,
           recipient: buyer.publicKey,
         },
@@ -273,6 +351,8 @@
         tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         escrowTokenPrize,
         fixedRaffle: fixedRaffle.publicKey,
+        config,
+        vault: null,
       },
     };
 
@@ -312,6 +392,7 @@
         }
       }
       await new Promise((r) => setTimeout(r, 5000));
+      console.log(await testLockRaffle());
       for (let i = 0; i < 10; i++) {
         try {
           console.log(await testPickWinner());
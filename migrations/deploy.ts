@@ -2,11 +2,44 @@
 // single deploy script that's invoked from the CLI, injecting a provider
 // configured from the workspace's Anchor.toml.
 
-const anchor = require("@project-serum/anchor");
+const anchor = require("@coral-xyz/anchor");
 
 module.exports = async function (provider) {
   // Configure client to use the provider.
   anchor.setProvider(provider);
 
-  // Add your deploy script here.
+  const program = anchor.workspace.RafflerAnchor;
+
+  const [config] = await anchor.web3.PublicKey.findProgramAddress(
+    [Buffer.from("config")],
+    program.programId
+  );
+
+  const [programData] = await anchor.web3.PublicKey.findProgramAddress(
+    [program.programId.toBytes()],
+    new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
+  // only the upgrade authority can set up the config, rotate keys later with updateConfig
+  if ((await provider.connection.getAccountInfo(config)) === null) {
+    await program.rpc.initializeConfig(
+      {
+        admin: provider.wallet.publicKey,
+        feeRecipient: provider.wallet.publicKey,
+        feeBps: 1000,
+        proceedsFeeBps: 0,
+        maxProceedsFeeBps: 500,
+        paused: false,
+      },
+      {
+        accounts: {
+          payer: provider.wallet.publicKey,
+          config,
+          program: program.programId,
+          programData,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+      }
+    );
+  }
 };
@@ -0,0 +1,22 @@
+use {
+    anchor_lang::prelude::*,
+    anchor_lang::solana_program::hash::hashv,
+};
+
+// a leaf is the wallet and how many tickets it may buy, 0 for no cap
+pub fn allowlist_leaf(buyer: &Pubkey, allocation: u64) -> [u8; 32] {
+    hashv(&[b"allowlist", buyer.as_ref(), &allocation.to_le_bytes()]).to_bytes()
+}
+
+// pairs are hashed in sorted order so proofs don't need to carry left/right
+pub fn verify_allowlist(root: &[u8; 32], leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+    let node = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            hashv(&[&node, sibling]).to_bytes()
+        } else {
+            hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+
+    node == *root
+}
@@ -37,12 +37,81 @@ pub enum CustomError {
     #[msg("The time parameters don't add up.")]
     TimeError,
 
-    #[msg("later")]
+    #[msg("The ticket account has to start as just its header")]
     FixedError,
 
     #[msg("The payment needs to come to an even number!")]
     DivisibleError,
 
     #[msg("There is an error with the given decimals")]
-    DecimalError
+    DecimalError,
+
+    #[msg("The raffle needs to be locked first")]
+    NotLocked,
+
+    #[msg("The raffle is already locked")]
+    AlreadyLocked,
+
+    #[msg("The secret doesn't match the commitment")]
+    BadReveal,
+
+    #[msg("Waiting on the randomness to be revealed")]
+    RevealPending,
+
+    #[msg("The oracle proof doesn't verify")]
+    BadProof,
+
+    #[msg("This ticket didn't win")]
+    NotWinner,
+
+    #[msg("This prize was already claimed")]
+    AlreadyClaimed,
+
+    #[msg("Not enough tickets were sold")]
+    MinTicketsNotReached,
+
+    #[msg("The raffle was cancelled, claim a refund instead")]
+    RaffleCancelled,
+
+    #[msg("The raffle isn't refunding")]
+    NotCancelled,
+
+    #[msg("No tickets left to refund")]
+    NothingToRefund,
+
+    #[msg("Buyers still have refunds to claim")]
+    RefundsPending,
+
+    #[msg("The protocol is paused")]
+    Paused,
+
+    #[msg("The fee is above the protocol maximum")]
+    FeeTooHigh,
+
+    #[msg("Every extra prize tier has to be closed first")]
+    PrizeTiersOpen,
+
+    #[msg("The prize metadata doesn't match what the raffle advertises")]
+    MetadataMismatch,
+
+    #[msg("The prize isn't part of a verified collection")]
+    UnverifiedCollection,
+
+    #[msg("This wallet isn't on the allowlist")]
+    NotAllowlisted,
+
+    #[msg("This wallet can't buy that many tickets")]
+    WalletLimit,
+
+    #[msg("The buyer doesn't hold what this raffle is gated on")]
+    GateFailed,
+
+    #[msg("The raffle hasn't been closed yet")]
+    RaffleOpen,
+
+    #[msg("The slot hash this draw needs is gone, the raffle can only refund")]
+    EntropyExpired,
+
+    #[msg("The raffle's prizes and winners don't fit in its account")]
+    RaffleTooLarge
 }
@@ -0,0 +1,203 @@
+use {
+    anchor_lang::prelude::*,
+    crate::state::*,
+};
+
+// one event per state change an indexer follows, each carries enough to track a raffle without reading its accounts
+// amounts are in base units of their mint unless noted
+
+#[event]
+pub struct RaffleCreated {
+    pub raffle: Pubkey,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub prize: Pubkey,
+    // the FixedTicketAccount holding the runs
+    pub tickets: Pubkey,
+    pub nonce: u64,
+    pub prize_quantity: u64,
+    pub per_win: u64,
+    pub price: u64,
+    pub cost_decimals: u8,
+    pub prize_decimals: u8,
+    pub start: i64,
+    pub end: i64,
+    pub max_entries: u64,
+    pub min_tickets: u64,
+    pub max_tickets_per_wallet: u64,
+    pub fee_bps: u16,
+    pub win_multiple: bool,
+    pub burn: bool,
+    pub fixed: bool,
+    pub native: bool,
+    pub commitment: [u8; 32],
+    pub source: RandomnessSource,
+    pub oracle: Pubkey,
+    pub collection: Pubkey,
+    pub allowlist: [u8; 32],
+    pub gate: TicketGate,
+}
+
+#[event]
+pub struct TicketsPurchased {
+    pub raffle: Pubkey,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    // the buyer holds tickets first_ticket..first_ticket + amount
+    pub first_ticket: u64,
+    pub tickets_purchased: u64,
+    pub cost: u64,
+    // what the buyer paid to grow the ticket account
+    pub rent_paid: u64,
+    // the wallet's first purchase in this raffle
+    pub new_buyer: bool,
+    pub unique_entries: u32,
+}
+
+#[event]
+pub struct WinnerSelected {
+    pub raffle: Pubkey,
+    // index into the raffle's winners, which decides the prize tier
+    pub slot: u32,
+    pub ticket: u64,
+    pub buyer: Pubkey,
+    pub mint: Pubkey,
+    // the draw is winning_index(seed, draw_nonce, tickets_purchased)
+    pub seed: [u8; 32],
+    pub draw_nonce: u64,
+    pub tickets_purchased: u64,
+    pub winners_selected: bool,
+}
+
+#[event]
+pub struct PrizeSent {
+    pub raffle: Pubkey,
+    pub slot: u32,
+    pub ticket: u64,
+    pub winner: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub sent_out: u8,
+}
+
+#[event]
+pub struct RaffleClosed {
+    pub raffle: Pubkey,
+    pub closed_by: Pubkey,
+    pub refunding: bool,
+    // cost tokens or lamports, after the protocol's cut
+    pub proceeds: u64,
+    pub fee: u64,
+    pub burned: u64,
+    pub returned: u64,
+    // main prize tokens that went back to the creator
+    pub prize_returned: u64,
+    pub bundle_returned: u8,
+    // set aside in the config for buyers to take back with close_entry
+    pub buyer_rent: u64,
+}
+
+// emitted alongside RaffleClosed when the admin closes past the usual checks
+#[event]
+pub struct RaffleForceClosed {
+    pub raffle: Pubkey,
+    pub admin: Pubkey,
+    pub unpaid_winners: u32,
+    pub unrefunded_tickets: u64,
+}
+
+// the admin's override of the raffle's cut, before the first ticket
+#[event]
+pub struct FeeSet {
+    pub raffle: Pubkey,
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct PrizeTierAdded {
+    pub raffle: Pubkey,
+    // index into the raffle's tiers, close_prize_tier takes it back
+    pub tier: u8,
+    pub mint: Pubkey,
+    // in units of `decimals`, like the raffle's own prize
+    pub quantity: u64,
+    pub per_win: u64,
+    pub decimals: u8,
+}
+
+#[event]
+pub struct BundleAdded {
+    pub raffle: Pubkey,
+    // only the nfts this call added, in the order they're drawn
+    pub mints: Vec<Pubkey>,
+    pub bundle_len: u8,
+}
+
+#[event]
+pub struct PrizeTierClosed {
+    pub raffle: Pubkey,
+    pub tier: u8,
+    pub mint: Pubkey,
+    pub closed_by: Pubkey,
+    // what went back to the creator, winners already paid kept theirs
+    pub returned: u64,
+}
+
+// the raffle went into refunding, see RefundClaimed for the buyers taking their tickets back
+#[event]
+pub struct RaffleCancelled {
+    pub raffle: Pubkey,
+    pub cancelled_by: Pubkey,
+    // sold under min_tickets or too few for every winner, anyone can cancel it then
+    pub undersold: bool,
+    // set_winner sent it to refunds after REVEAL_TIMEOUT without a seed
+    pub timed_out: bool,
+    pub tickets_purchased: u64,
+}
+
+#[event]
+pub struct RefundClaimed {
+    pub raffle: Pubkey,
+    pub buyer: Pubkey,
+    pub tickets: u64,
+    pub amount: u64,
+    pub tickets_refunded: u64,
+}
+
+#[event]
+pub struct RaffleLocked {
+    pub raffle: Pubkey,
+    pub locked_by: Pubkey,
+    // the seed mixes in the hash of the first slot ENTROPY_DELAY past this one
+    pub lock_slot: u64,
+    pub locked_at: i64,
+    pub tickets_purchased: u64,
+}
+
+#[event]
+pub struct RandomnessRequested {
+    pub raffle: Pubkey,
+    pub request: Pubkey,
+    pub oracle: Pubkey,
+    // what the oracle has to sign
+    pub alpha: [u8; 32],
+}
+
+#[event]
+pub struct RandomnessFulfilled {
+    pub raffle: Pubkey,
+    pub request: Pubkey,
+    pub output: [u8; 32],
+    // the seed mixes in the hash of the first slot ENTROPY_DELAY past this one
+    pub fulfilled_slot: u64,
+}
+
+// the seed is fixed, from the creator's reveal or once the oracle's output meets its slot hash in set_winner
+#[event]
+pub struct SeedRevealed {
+    pub raffle: Pubkey,
+    pub source: RandomnessSource,
+    pub slot_hash: [u8; 32],
+    pub seed: [u8; 32],
+}
@@ -1,11 +1,20 @@
+#![allow(clippy::result_large_err)]
+
 pub mod error;
 pub mod state;
 pub mod security;
+pub mod randomness;
+pub mod transfer_fee;
+pub mod metadata;
+pub mod allowlist;
+pub mod tickets;
+pub mod events;
 
 use {
     anchor_lang::prelude::*,
-    crate::{error::*, state::*},
-    std::collections::BTreeSet,
+    anchor_spl::token::spl_token::native_mint,
+    anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+    crate::{error::*, state::*, randomness::*, transfer_fee::*, metadata::*, allowlist::*, tickets::*, events::*},
 };
 
 declare_id!("RafXcAJfB3wVzyH7cHuDqyVjYeQ4Qy8RjQEbCPvttK9");
@@ -14,21 +23,75 @@ declare_id!("RafXcAJfB3wVzyH7cHuDqyVjYeQ4Qy8RjQEbCPvttK9");
 pub mod raffler_anchor {
     use super::*;
 
+    pub fn initialize_config(ctx: Context<InitializeConfig>, data: ConfigData) -> Result<()> {
+        if !data.is_valid() {
+            return err!(CustomError::InputError);
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.admin = data.admin;
+        config.fee_recipient = data.fee_recipient;
+        config.fee_bps = data.fee_bps;
+        config.proceeds_fee_bps = data.proceeds_fee_bps;
+        config.max_proceeds_fee_bps = data.max_proceeds_fee_bps;
+        config.paused = data.paused;
+        config.bump = ctx.bumps.config;
+
+        Ok(())
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, data: ConfigData) -> Result<()> {
+        if !data.is_valid() {
+            return err!(CustomError::InputError);
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.admin = data.admin;
+        config.fee_recipient = data.fee_recipient;
+        config.fee_bps = data.fee_bps;
+        config.proceeds_fee_bps = data.proceeds_fee_bps;
+        config.max_proceeds_fee_bps = data.max_proceeds_fee_bps;
+        config.paused = data.paused;
+
+        Ok(())
+    }
+
+    // admin override of a single raffle's cut, existing raffles keep the fee they were created with otherwise
+    pub fn set_raffle_fee(ctx: Context<SetRaffleFee>, fee_bps: u16) -> Result<()> {
+        if fee_bps > ctx.accounts.config.max_proceeds_fee_bps {
+            return err!(CustomError::FeeTooHigh);
+        }
+
+        // buyers paid in under the fee that was set then
+        if ctx.accounts.raffle.tickets_purchased > 0 {
+            return err!(CustomError::RaffleStarted);
+        }
+
+        ctx.accounts.raffle.fee_bps = fee_bps;
+
+        emit!(FeeSet {
+            raffle: ctx.accounts.raffle.key(),
+            admin: ctx.accounts.payer.key(),
+            fee_bps,
+        });
+
+        Ok(())
+    }
+
     pub fn create_raffle(ctx: Context<CreateRaffle>, data: CreateRaffleData) -> Result<()> {
+        if ctx.accounts.config.paused {
+            return err!(CustomError::Paused);
+        }
+
         if data.start >= data.end || data.prize_quantity == 0 || data.price == 0 || data.per_win == 0 || ctx.accounts.token_prize.amount < data.prize_quantity {
             return err!(CustomError::InputError);
         }
 
         // this needs to be divisible
-        if data.prize_quantity % data.per_win != 0 {
+        if data.prize_quantity.checked_rem(data.per_win) != Some(0) {
             return err!(CustomError::DivisibleError);
         }
 
-        // later
-        if data.fixed == false {
-            return err!(CustomError::FixedError);
-        }
-
         let clock = Clock::get()?;
 
         // raffles can't be longer than two weeks unless they're set to be open forever, in which case they must sell out
@@ -36,11 +99,58 @@ pub mod raffler_anchor {
             return err!(CustomError::TimeError);
         }
 
+        // dynamic raffles have no cap, they can never sell out so they need a real end
+        let max_entries = if data.fixed { data.max_entries } else { u64::MAX };
+
+        if !data.fixed && data.end == i64::MAX {
+            return err!(CustomError::TimeError);
+        }
+
+        if max_entries == 0 || data.min_tickets > max_entries {
+            return err!(CustomError::InputError);
+        }
+
+        if data.source == RandomnessSource::Oracle && data.oracle == Pubkey::default() {
+            return err!(CustomError::InputError);
+        }
+
+        // native raffles are paid in lamports, keyed on the native mint so the raffle seeds and decimals still work
+        if data.native && (ctx.accounts.mint_cost.key() != native_mint::ID || data.burn) {
+            return err!(CustomError::InputError);
+        }
+
+        // the ticket account starts as just its header and grows as tickets sell, buyers pay for the runs they add
+        if ctx.accounts.fixed_raffle.to_account_info().data_len() != RAFFLE_ENTRY_OFFSET {
+            return err!(CustomError::FixedError);
+        }
+
+        // the advertised uri has to be the prize's on-chain one and the prize has to sit in a verified collection
+        let mut collection = Pubkey::default();
+
+        if data.verify_metadata {
+            let Some(prize_metadata) = &ctx.accounts.prize_metadata else {
+                return err!(CustomError::InputError);
+            };
+
+            if prize_metadata.key() != metadata_address(&ctx.accounts.mint_prize.key()) || prize_metadata.owner != &METADATA_PROGRAM_ID {
+                return err!(CustomError::InputError);
+            }
+
+            let metadata = MintMetadata::from_account_data(&prize_metadata.data.borrow())?;
+
+            if metadata.mint != ctx.accounts.mint_prize.key() || metadata.uri() != data.nft_uri {
+                return err!(CustomError::MetadataMismatch);
+            }
+
+            collection = metadata.verified_collection().ok_or(CustomError::UnverifiedCollection)?;
+        }
+
         let raffle = &mut ctx.accounts.raffle;
         raffle.id = raffle.key();
         raffle.owner = *ctx.accounts.payer.key;
         raffle.mint = ctx.accounts.mint_cost.key();
         raffle.prize = ctx.accounts.mint_prize.key();
+        raffle.nonce = data.nonce;
         raffle.prize_quantity = data.prize_quantity;
         raffle.tickets_purchased = 0;
         raffle.cost_decimals = data.cost_decimals;
@@ -50,150 +160,632 @@ pub mod raffler_anchor {
         raffle.date_created = clock.unix_timestamp;
         raffle.end = data.end;
         raffle.ticket_count = 0;
-        raffle.max_entries = data.max_entries;
+        raffle.max_entries = max_entries;
+        raffle.min_tickets = data.min_tickets;
+        raffle.max_tickets_per_wallet = data.max_tickets_per_wallet;
         raffle.per_win = data.per_win;
+        raffle.fee_bps = ctx.accounts.config.proceeds_fee_bps;
         raffle.win_multiple = data.win_multiple;
         raffle.description = data.description;
-        raffle.bump = *ctx.bumps.get("raffle").unwrap();
+        raffle.bump = ctx.bumps.raffle;
         raffle.burn = data.burn;
         raffle.nft_image = data.nft_image;
         raffle.nft_uri = data.nft_uri;
         raffle.fixed = data.fixed;
+        raffle.native = data.native;
+        raffle.commitment = data.commitment;
+        raffle.source = data.source;
+        raffle.oracle = data.oracle;
+        raffle.collection = collection;
+        raffle.allowlist = data.allowlist.unwrap_or_default();
+        raffle.gate = data.gate;
+        raffle.tiers = vec![PrizeTier {
+            mint: raffle.prize,
+            quantity: data.prize_quantity,
+            per_win: data.per_win,
+            decimals: data.prize_decimals,
+            closed: false
+        }];
 
         if data.cost_decimals > ctx.accounts.mint_cost.decimals || data.prize_decimals > ctx.accounts.mint_prize.decimals {
             return err!(CustomError::DecimalError);
         }
 
+        // the account is created small, it has to hold every winner once they're drawn
+        fit_raffle(raffle, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+
         let prize_decimals = (ctx.accounts.mint_prize.decimals - raffle.prize_decimals) as u32;
 
-        anchor_spl::token::transfer(
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::token::Transfer {
+        // the escrow has to end up with the full prize, any transfer fee is on the creator
+        let prize_amount = gross_amount(&ctx.accounts.mint_prize.to_account_info(), raffle.prize_quantity * 10_u64.pow(prize_decimals))?;
+
+        if ctx.accounts.token_prize.amount < prize_amount {
+            return err!(CustomError::InputError);
+        }
+
+        anchor_spl::token_interface::transfer_checked(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
                 from: ctx.accounts.token_prize.to_account_info(),
+                mint: ctx.accounts.mint_prize.to_account_info(),
                 to:  ctx.accounts.escrow_token_prize.to_account_info(),
                 authority:  ctx.accounts.payer.to_account_info()
             }),
-            raffle.prize_quantity * 10_u64.pow(prize_decimals),
+            prize_amount,
+            ctx.accounts.mint_prize.decimals,
         )?;
 
-        ctx.accounts.fixed_raffle.raffle_id = ctx.accounts.raffle.key();
+        // the vault has to stay rent exempt on its own, the creator covers that up front and gets it back on close
+        if data.native {
+            let Some(vault) = &ctx.accounts.vault else {
+                return err!(CustomError::InputError);
+            };
+
+            let rent = Rent::get()?.minimum_balance(0).saturating_sub(vault.lamports());
+
+            if rent > 0 {
+                anchor_lang::system_program::transfer(
+                    CpiContext::new(ctx.accounts.system_program.to_account_info(), anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: vault.to_account_info()
+                    }),
+                    rent,
+                )?;
+            }
+
+            ctx.accounts.raffle.vault_bump = ctx.bumps.vault;
+        }
+
+        ctx.accounts.fixed_raffle.load_init()?.raffle_id = ctx.accounts.raffle.key();
+
+        let raffle = &ctx.accounts.raffle;
+
+        emit!(RaffleCreated {
+            raffle: raffle.key(),
+            owner: raffle.owner,
+            mint: raffle.mint,
+            prize: raffle.prize,
+            tickets: ctx.accounts.fixed_raffle.key(),
+            nonce: raffle.nonce,
+            prize_quantity: raffle.prize_quantity,
+            per_win: raffle.per_win,
+            price: raffle.price,
+            cost_decimals: raffle.cost_decimals,
+            prize_decimals: raffle.prize_decimals,
+            start: raffle.start,
+            end: raffle.end,
+            max_entries: raffle.max_entries,
+            min_tickets: raffle.min_tickets,
+            max_tickets_per_wallet: raffle.max_tickets_per_wallet,
+            fee_bps: raffle.fee_bps,
+            win_multiple: raffle.win_multiple,
+            burn: raffle.burn,
+            fixed: raffle.fixed,
+            native: raffle.native,
+            commitment: raffle.commitment,
+            source: raffle.source,
+            oracle: raffle.oracle,
+            collection: raffle.collection,
+            allowlist: raffle.allowlist,
+            gate: raffle.gate,
+        });
 
         Ok(())
     }
 
-    pub fn close_raffle(ctx: Context<CloseRaffle>, force_close: bool) -> Result<()> {
-        let is_admin = ctx.accounts.payer.key.to_string() == MOON_KEY && force_close;
-        let ticket_account = ctx.accounts.fixed_raffle.to_account_info();
-        let ticket_data = ticket_account.data.borrow();
+    // extra prize tiers go in after create and before the first ticket, winners are drawn for them in the order they were added
+    pub fn add_prize_tier(ctx: Context<AddPrizeTier>, data: PrizeTierData) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
+        let mint_prize = ctx.accounts.mint_prize.key();
+
+        if raffle.tickets_purchased > 0 {
+            return err!(CustomError::RaffleStarted);
+        }
+
+        if raffle.refunding {
+            return err!(CustomError::RaffleCancelled);
+        }
+
+        if data.quantity == 0 || data.per_win == 0 || raffle.tiers.len() >= MAX_PRIZE_TIERS {
+            return err!(CustomError::InputError);
+        }
+
+        if data.quantity.checked_rem(data.per_win) != Some(0) {
+            return err!(CustomError::DivisibleError);
+        }
+
+        if data.decimals > ctx.accounts.mint_prize.decimals {
+            return err!(CustomError::DecimalError);
+        }
+
+        // every tier gets its own escrow, which is the raffle's ATA for the mint
+        if raffle.tiers.iter().any(|tier| tier.mint == mint_prize) || raffle.bundle.contains(&mint_prize) {
+            return err!(CustomError::InputError);
+        }
+
+        if ctx.accounts.escrow_token_prize.key() != anchor_spl::associated_token::get_associated_token_address_with_program_id(&raffle.key(), &mint_prize, ctx.accounts.token_program.key) {
+            return err!(CustomError::InputError);
+        }
+
+        if ctx.accounts.escrow_token_prize.to_account_info().data_is_empty() {
+            anchor_spl::associated_token::create(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::associated_token::Create {
+                    payer: ctx.accounts.payer.to_account_info(),
+                    associated_token: ctx.accounts.escrow_token_prize.to_account_info(),
+                    authority: raffle.to_account_info(),
+                    mint: ctx.accounts.mint_prize.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info()
+                }),
+            )?;
+        }
+
+        let prize_decimals = (ctx.accounts.mint_prize.decimals - data.decimals) as u32;
+        let prize_amount = gross_amount(&ctx.accounts.mint_prize.to_account_info(), data.quantity * 10_u64.pow(prize_decimals))?;
+
+        anchor_spl::token_interface::transfer_checked(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                from: ctx.accounts.token_prize.to_account_info(),
+                mint: ctx.accounts.mint_prize.to_account_info(),
+                to:  ctx.accounts.escrow_token_prize.to_account_info(),
+                authority:  ctx.accounts.payer.to_account_info()
+            }),
+            prize_amount,
+            ctx.accounts.mint_prize.decimals,
+        )?;
+
+        raffle.tiers.push(PrizeTier {
+            mint: mint_prize,
+            quantity: data.quantity,
+            per_win: data.per_win,
+            decimals: data.decimals,
+            closed: false
+        });
+
+        // the new tier's winners need room too
+        fit_raffle(raffle, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+
+        emit!(PrizeTierAdded {
+            raffle: raffle.key(),
+            tier: (raffle.tiers.len() - 1) as u8,
+            mint: mint_prize,
+            quantity: data.quantity,
+            per_win: data.per_win,
+            decimals: data.decimals,
+        });
+
+        Ok(())
+    }
+
+    // a bundle hands out distinct nfts, one per winner after all the tiers, each in its own escrow
+    pub fn add_bundle<'info>(ctx: Context<'_, '_, '_, 'info, AddBundle<'info>>) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
+        let nfts = ctx.remaining_accounts;
+
+        if raffle.tickets_purchased > 0 {
+            return err!(CustomError::RaffleStarted);
+        }
 
-        if ctx.accounts.fixed_raffle.owner != &ID || &ticket_data[8..40] != ctx.accounts.raffle.id.as_ref() {
+        if raffle.refunding {
+            return err!(CustomError::RaffleCancelled);
+        }
+
+        if nfts.is_empty() || nfts.len().checked_rem(3) != Some(0) || raffle.bundle.len() + nfts.len() / 3 > MAX_BUNDLE_NFTS {
+            return err!(CustomError::InputError);
+        }
+
+        for accounts in nfts.chunks(3) {
+            let (mint, token_prize, escrow) = (&accounts[0], &accounts[1], &accounts[2]);
+
+            // the bundle shares the raffle prize's token program, close_raffle returns it all through that
+            if mint.owner != ctx.accounts.token_program.key {
+                return err!(CustomError::InputError);
+            }
+
+            let nft = anchor_spl::token_interface::Mint::try_deserialize(&mut &mint.data.borrow()[..])?;
+
+            if nft.decimals != 0 || nft.supply != 1 {
+                return err!(CustomError::InputError);
+            }
+
+            if raffle.tiers.iter().any(|tier| tier.mint == *mint.key) || raffle.bundle.contains(mint.key) {
+                return err!(CustomError::InputError);
+            }
+
+            if *escrow.key != anchor_spl::associated_token::get_associated_token_address_with_program_id(&raffle.key(), mint.key, ctx.accounts.token_program.key) {
+                return err!(CustomError::InputError);
+            }
+
+            if escrow.data_is_empty() {
+                anchor_spl::associated_token::create(
+                    CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::associated_token::Create {
+                        payer: ctx.accounts.payer.to_account_info(),
+                        associated_token: escrow.clone(),
+                        authority: raffle.to_account_info(),
+                        mint: mint.clone(),
+                        system_program: ctx.accounts.system_program.to_account_info(),
+                        token_program: ctx.accounts.token_program.to_account_info()
+                    }),
+                )?;
+            }
+
+            anchor_spl::token_interface::transfer_checked(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                    from: token_prize.clone(),
+                    mint: mint.clone(),
+                    to: escrow.clone(),
+                    authority: ctx.accounts.payer.to_account_info()
+                }),
+                gross_amount(mint, 1)?,
+                0,
+            )?;
+
+            raffle.bundle.push(*mint.key);
+        }
+
+        // every nft adds a winner
+        fit_raffle(raffle, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+
+        emit!(BundleAdded {
+            raffle: raffle.key(),
+            mints: nfts.chunks(3).map(|accounts| *accounts[0].key).collect(),
+            bundle_len: raffle.bundle.len() as u8,
+        });
+
+        Ok(())
+    }
+
+    // returns what's left of an extra tier and closes its escrow, tier 0 goes back through close_raffle
+    pub fn close_prize_tier(ctx: Context<ClosePrizeTier>, tier: u8, force_close: bool) -> Result<()> {
+        let is_admin = ctx.accounts.payer.key() == ctx.accounts.config.admin && force_close;
+
+        if force_close && !is_admin {
             return err!(CustomError::InputError);
         }
 
+        let raffle = &ctx.accounts.raffle;
+        let tier = tier as usize;
+
+        if tier == 0 || tier >= raffle.tiers.len() || raffle.tiers[tier].closed || raffle.tiers[tier].mint != ctx.accounts.mint_prize.key() {
+            return err!(CustomError::InputError);
+        }
+
+        let slots = raffle.tier_slots(tier);
+        let paid = raffle.claimed.iter().skip(slots.start).take(slots.len()).filter(|claimed| **claimed).count();
+
+        if raffle.refunding {
+            if raffle.tickets_refunded < raffle.tickets_purchased && !is_admin {
+                return err!(CustomError::RefundsPending);
+            }
+        } else if raffle.tickets_purchased == 0 {
+            // nothing sold, but it could still sell until the end
+            if Clock::get()?.unix_timestamp <= raffle.end && !is_admin {
+                return err!(CustomError::RaffleGoing);
+            }
+        } else if paid < slots.len() && !is_admin {
+            return err!(CustomError::CantScam);
+        }
+
+        let nonce = raffle.nonce.to_le_bytes();
+        let seeds: &[&[_]] = &[&[
+            raffle.owner.as_ref(),
+            raffle.mint.as_ref(),
+            raffle.prize.as_ref(),
+            &nonce,
+            &[raffle.bump]
+        ]];
+
+        let prize_tier = &raffle.tiers[tier];
+        let prize_decimals = (ctx.accounts.mint_prize.decimals - prize_tier.decimals) as u32;
+        let leftover = (prize_tier.quantity - prize_tier.per_win * paid as u64) * 10_u64.pow(prize_decimals);
+
+        if leftover > 0 {
+            anchor_spl::token_interface::transfer_checked(
+                CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                        from: ctx.accounts.escrow_token_prize.to_account_info(),
+                        mint: ctx.accounts.mint_prize.to_account_info(),
+                        to: ctx.accounts.token_prize.to_account_info(),
+                        authority: raffle.to_account_info()
+                    },
+                    seeds
+                ),
+                leftover,
+                ctx.accounts.mint_prize.decimals,
+            )?;
+        }
+
+        harvest_withheld(&ctx.accounts.token_program.to_account_info(), &ctx.accounts.mint_prize.to_account_info(), &ctx.accounts.escrow_token_prize.to_account_info())?;
+
+        anchor_spl::token_interface::close_account(
+            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::CloseAccount {
+                    account: ctx.accounts.escrow_token_prize.to_account_info(),
+                    destination: ctx.accounts.fee_recipient.to_account_info(),
+                    authority: raffle.to_account_info()
+                },
+                seeds
+            ),
+        )?;
+
+        ctx.accounts.raffle.tiers[tier].closed = true;
+
+        emit!(PrizeTierClosed {
+            raffle: ctx.accounts.raffle.key(),
+            tier: tier as u8,
+            mint: ctx.accounts.mint_prize.key(),
+            closed_by: ctx.accounts.payer.key(),
+            returned: leftover,
+        });
+
+        Ok(())
+    }
+
+    // bundle raffles pass [mint, creator token account, raffle escrow] for every bundle nft as remaining accounts
+    pub fn close_raffle<'info>(ctx: Context<'_, '_, '_, 'info, CloseRaffle<'info>>, force_close: bool) -> Result<()> {
+        let is_admin = ctx.accounts.payer.key() == ctx.accounts.config.admin && force_close;
+
         if force_close && !is_admin {
             return err!(CustomError::InputError);
         }
 
         let raffle = &ctx.accounts.raffle;
 
-        if raffle.sent_out == 0 && raffle.tickets_purchased > 0 && !is_admin {
+        // cancelled raffles close once every buyer got their money back
+        if raffle.refunding {
+            if raffle.tickets_refunded < raffle.tickets_purchased && !is_admin {
+                return err!(CustomError::RefundsPending);
+            }
+        } else if raffle.sent_out == 0 && raffle.tickets_purchased > 0 && !is_admin {
             return err!(CustomError::RaffleStarted);
         }
 
         // they need to pay out all winners
-        if raffle.winners.len() != 0 && !is_admin {
+        if raffle.claimed.iter().any(|claimed| !claimed) && !is_admin {
             return err!(CustomError::CantScam);
         }
 
+        // the raffle signs for the tier escrows, once it's gone so is anything left in them
+        if raffle.tiers.iter().skip(1).any(|tier| !tier.closed) {
+            return err!(CustomError::PrizeTiersOpen);
+        }
+
+        let nonce = raffle.nonce.to_le_bytes();
         let seeds: &[&[_]] = &[&[
             ctx.accounts.raffle.owner.as_ref(),
             ctx.accounts.mint_cost.to_account_info().key.as_ref(),
             ctx.accounts.mint_prize.to_account_info().key.as_ref(),
+            &nonce,
             &[raffle.bump]
         ]];
 
         let prize_decimals = (ctx.accounts.mint_prize.decimals - raffle.prize_decimals) as u32;
         let cost_decimals = (ctx.accounts.mint_cost.decimals - raffle.cost_decimals) as u32;
 
+        // only the main prize's winners come out of this escrow
+        let paid = raffle.claimed.iter().take(raffle.tier_slots(0).len()).filter(|claimed| **claimed).count();
+
+        // whatever buyers paid in and didn't take back as refunds, the protocol cut comes off the top before any burn
+        let proceeds = (raffle.tickets_purchased - raffle.tickets_refunded) * raffle.price * 10_u64.pow(cost_decimals);
+        let fee = (proceeds as u128 * raffle.fee_bps as u128 / MAX_BPS as u128) as u64;
+        let proceeds = proceeds - fee;
+        let burned = if raffle.burn && !raffle.native && !is_admin { proceeds } else { 0 };
+
+        // draw back the prize tokens if there are any left over
+        let prize_returned = (raffle.prize_quantity - (raffle.per_win * paid as u64)) * 10_u64.pow(prize_decimals);
+
         // take prize tokens back from escrow
-        anchor_spl::token::transfer(
-            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token::Transfer {
+        anchor_spl::token_interface::transfer_checked(
+            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
                     from:  ctx.accounts.escrow_token_prize.to_account_info(),
+                    mint: ctx.accounts.mint_prize.to_account_info(),
                     to: ctx.accounts.token_prize.to_account_info(),
                     authority:  ctx.accounts.raffle.to_account_info()
                 },
                 seeds
             ),
-            // draw back the prize tokens if there are any left over
-            (raffle.prize_quantity - (raffle.per_win * raffle.sent_out as u64)) * 10_u64.pow(prize_decimals),
+            prize_returned,
+            ctx.accounts.mint_prize.decimals,
         )?;
 
-        if raffle.burn && !is_admin {
-            // burn
-            anchor_spl::token::burn(
-                CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token::Burn {
-                        mint: ctx.accounts.mint_cost.to_account_info(),
-                        from:  ctx.accounts.escrow_token_cost.to_account_info(),
-                        authority:  ctx.accounts.raffle.to_account_info()
+        if raffle.native {
+            let Some(vault) = &ctx.accounts.vault else {
+                return err!(CustomError::InputError);
+            };
+
+            let raffle_key = raffle.key();
+            let vault_seeds: &[&[_]] = &[&[VAULT_SEED, raffle_key.as_ref(), &[raffle.vault_bump]]];
+
+            if fee > 0 {
+                anchor_lang::system_program::transfer(
+                    CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), anchor_lang::system_program::Transfer {
+                            from: vault.to_account_info(),
+                            to: ctx.accounts.fee_recipient.to_account_info()
+                        },
+                        vault_seeds
+                    ),
+                    fee,
+                )?;
+            }
+
+            // the proceeds and the vault's own rent go back to the creator together, which leaves the vault empty
+            anchor_lang::system_program::transfer(
+                CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), anchor_lang::system_program::Transfer {
+                        from: vault.to_account_info(),
+                        to: ctx.accounts.owner.to_account_info()
                     },
-                    seeds
+                    vault_seeds
                 ),
-                raffle.tickets_purchased * 10_u64.pow(cost_decimals),
+                vault.lamports(),
             )?;
         } else {
-            // take paid tokens back from escrow
-            anchor_spl::token::transfer(
-                CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token::Transfer {
-                        from:  ctx.accounts.escrow_token_cost.to_account_info(),
-                        to: ctx.accounts.token_cost.to_account_info(),
-                        authority:  ctx.accounts.raffle.to_account_info()
+            let (Some(token_cost), Some(escrow_token_cost), Some(fee_token_cost)) = (&ctx.accounts.token_cost, &ctx.accounts.escrow_token_cost, &ctx.accounts.fee_token_cost) else {
+                return err!(CustomError::InputError);
+            };
+
+            if fee > 0 {
+                anchor_spl::token_interface::transfer_checked(
+                    CpiContext::new_with_signer(ctx.accounts.cost_token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                            from:  escrow_token_cost.to_account_info(),
+                            mint: ctx.accounts.mint_cost.to_account_info(),
+                            to: fee_token_cost.to_account_info(),
+                            authority:  ctx.accounts.raffle.to_account_info()
+                        },
+                        seeds
+                    ),
+                    fee,
+                    ctx.accounts.mint_cost.decimals,
+                )?;
+            }
+
+            if raffle.burn && !is_admin {
+                // burn
+                anchor_spl::token_interface::burn(
+                    CpiContext::new_with_signer(ctx.accounts.cost_token_program.to_account_info(), anchor_spl::token_interface::Burn {
+                            mint: ctx.accounts.mint_cost.to_account_info(),
+                            from:  escrow_token_cost.to_account_info(),
+                            authority:  ctx.accounts.raffle.to_account_info()
+                        },
+                        seeds
+                    ),
+                    proceeds,
+                )?;
+            } else {
+                // take paid tokens back from escrow
+                anchor_spl::token_interface::transfer_checked(
+                    CpiContext::new_with_signer(ctx.accounts.cost_token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                            from:  escrow_token_cost.to_account_info(),
+                            mint: ctx.accounts.mint_cost.to_account_info(),
+                            to: token_cost.to_account_info(),
+                            authority:  ctx.accounts.raffle.to_account_info()
+                        },
+                        seeds
+                    ),
+                    proceeds,
+                    ctx.accounts.mint_cost.decimals,
+                )?;
+            }
+
+            harvest_withheld(&ctx.accounts.cost_token_program.to_account_info(), &ctx.accounts.mint_cost.to_account_info(), &escrow_token_cost.to_account_info())?;
+
+            anchor_spl::token_interface::close_account(
+                CpiContext::new_with_signer(ctx.accounts.cost_token_program.to_account_info(), anchor_spl::token_interface::CloseAccount {
+                        account: escrow_token_cost.to_account_info(),
+                        destination: ctx.accounts.payer.to_account_info(),
+                        authority: ctx.accounts.raffle.to_account_info()
                     },
                     seeds
                 ),
-                raffle.tickets_purchased * 10_u64.pow(cost_decimals),
             )?;
         }
 
-        anchor_spl::token::close_account(
-            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token::CloseAccount {
-                    account: ctx.accounts.escrow_token_cost.to_account_info(),
-                    destination: ctx.accounts.payer.to_account_info(),
-                    authority: ctx.accounts.raffle.to_account_info()
-                },
-                seeds
-            ),
-        )?;
+        harvest_withheld(&ctx.accounts.token_program.to_account_info(), &ctx.accounts.mint_prize.to_account_info(), &ctx.accounts.escrow_token_prize.to_account_info())?;
 
-        anchor_spl::token::close_account(
-            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token::CloseAccount {
+        anchor_spl::token_interface::close_account(
+            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::CloseAccount {
                     account: ctx.accounts.escrow_token_prize.to_account_info(),
-                    destination: ctx.accounts.moon.to_account_info(),
+                    destination: ctx.accounts.fee_recipient.to_account_info(),
                     authority: ctx.accounts.raffle.to_account_info()
                 },
                 seeds
             ),
         )?;
 
+        // every bundle escrow gets closed, nfts nobody won go back with the rest of the prize
+        if ctx.remaining_accounts.len() != raffle.bundle.len() * 3 {
+            return err!(CustomError::InputError);
+        }
+
+        for (index, accounts) in ctx.remaining_accounts.chunks(3).enumerate() {
+            let (mint, token_prize, escrow) = (&accounts[0], &accounts[1], &accounts[2]);
+
+            if *mint.key != raffle.bundle[index] || *escrow.key != anchor_spl::associated_token::get_associated_token_address_with_program_id(&raffle.key(), mint.key, ctx.accounts.token_program.key) {
+                return err!(CustomError::InputError);
+            }
+
+            if raffle.claimed.get(raffle.bundle_slot(index)) != Some(&true) {
+                let destination = anchor_spl::token_interface::TokenAccount::try_deserialize(&mut &token_prize.data.borrow()[..])?;
+
+                if destination.owner != ctx.accounts.token_prize.owner || destination.mint != *mint.key {
+                    return err!(CustomError::InputError);
+                }
+
+                anchor_spl::token_interface::transfer_checked(
+                    CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                            from: escrow.clone(),
+                            mint: mint.clone(),
+                            to: token_prize.clone(),
+                            authority: ctx.accounts.raffle.to_account_info()
+                        },
+                        seeds
+                    ),
+                    1,
+                    0,
+                )?;
+            }
+
+            harvest_withheld(&ctx.accounts.token_program.to_account_info(), mint, escrow)?;
+
+            anchor_spl::token_interface::close_account(
+                CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::CloseAccount {
+                        account: escrow.clone(),
+                        destination: ctx.accounts.fee_recipient.to_account_info(),
+                        authority: ctx.accounts.raffle.to_account_info()
+                    },
+                    seeds
+                ),
+            )?;
+        }
+
+        emit!(RaffleClosed {
+            raffle: raffle.key(),
+            closed_by: ctx.accounts.payer.key(),
+            refunding: raffle.refunding,
+            proceeds,
+            fee,
+            burned,
+            returned: proceeds - burned,
+            prize_returned,
+            bundle_returned: (0..raffle.bundle.len()).filter(|index| raffle.claimed.get(raffle.bundle_slot(*index)) != Some(&true)).count() as u8,
+            buyer_rent: raffle.buyer_rent,
+        });
+
+        if is_admin {
+            emit!(RaffleForceClosed {
+                raffle: raffle.key(),
+                admin: ctx.accounts.payer.key(),
+                unpaid_winners: raffle.claimed.iter().filter(|claimed| !**claimed).count() as u32,
+                unrefunded_tickets: if raffle.refunding { raffle.tickets_purchased - raffle.tickets_refunded } else { 0 },
+            });
+        }
+
+        let buyer_rent = ctx.accounts.raffle.buyer_rent;
+        let config = ctx.accounts.config.to_account_info();
+
         let raffle = ctx.accounts.raffle.to_account_info();
         let fixed_raffle = ctx.accounts.fixed_raffle.to_account_info();
 
-        let payer = ctx.accounts.payer.to_account_info();
-        let moon = ctx.accounts.moon.to_account_info();
+        let owner = ctx.accounts.owner.to_account_info();
+        let fee_recipient = ctx.accounts.fee_recipient.to_account_info();
 
         let mut escrow_lams = raffle.lamports.borrow_mut();
         let mut fixed_raffle_lams = fixed_raffle.lamports.borrow_mut();
 
-        if payer.key == moon.key {
-            let mut payer_lams  = payer.lamports.borrow_mut();
-            **payer_lams += **escrow_lams;
-            **payer_lams += **fixed_raffle_lams;
+        // what buyers paid to grow the ticket account waits in the config until they close their entries
+        **config.lamports.borrow_mut() += buyer_rent;
+        **fixed_raffle_lams -= buyer_rent;
+
+        if owner.key == fee_recipient.key {
+            let mut owner_lams  = owner.lamports.borrow_mut();
+            **owner_lams += **escrow_lams;
+            **owner_lams += **fixed_raffle_lams;
         } else {
-            let mut moon_lams = moon.lamports.borrow_mut();
-            let mut payer_lams  = payer.lamports.borrow_mut();
+            let mut fee_recipient_lams = fee_recipient.lamports.borrow_mut();
+            let mut owner_lams  = owner.lamports.borrow_mut();
+
+            let fee = (**fixed_raffle_lams as u128 * ctx.accounts.config.fee_bps as u128 / MAX_BPS as u128) as u64;
 
-            **moon_lams += **escrow_lams;
-            **moon_lams += **fixed_raffle_lams / 10;
+            **fee_recipient_lams += **escrow_lams;
+            **fee_recipient_lams += fee;
 
-            **payer_lams += **fixed_raffle_lams - **fixed_raffle_lams / 10;
+            **owner_lams += **fixed_raffle_lams - fee;
         }
 
         **escrow_lams = 0;
@@ -202,25 +794,38 @@ pub mod raffler_anchor {
         Ok(())
     }
 
-    pub fn buy_ticket(ctx: Context<BuyTicket>, amount: u64) -> Result<()> {
+    // allowlisted raffles need the payer's allocation and a proof of it, everyone else passes 0 and an empty proof
+    pub fn buy_ticket(ctx: Context<BuyTicket>, amount: u64, allocation: u64, proof: Vec<[u8; 32]>) -> Result<()> {
         let raffle = &mut ctx.accounts.raffle;
         let ticket_account = ctx.accounts.fixed_raffle.to_account_info();
-        let ticket_data = &mut ticket_account.data.borrow_mut();
 
-        if ctx.accounts.fixed_raffle.owner != &ID || &ticket_data[8..40] != raffle.id.as_ref() || raffle.owner == *ctx.accounts.payer.key {
+        if raffle.owner == *ctx.accounts.payer.key {
             return err!(CustomError::InputError);
         }
 
+        if ctx.accounts.config.paused {
+            return err!(CustomError::Paused);
+        }
+
         let clock = Clock::get()?;
 
         if amount > 1200 {
             return err!(CustomError::TooMany);
         }
 
+        // an empty purchase would still create the entry and count the wallet as a buyer
+        if amount == 0 {
+            return err!(CustomError::InputError);
+        }
+
         if clock.unix_timestamp > raffle.end {
             return err!(CustomError::TooLate);
         }
 
+        if raffle.refunding {
+            return err!(CustomError::RaffleCancelled);
+        }
+
         if clock.unix_timestamp < raffle.start {
             return err!(CustomError::TooEarly);
         }
@@ -229,48 +834,168 @@ pub mod raffler_anchor {
             return err!(CustomError::NotEnough);
         }
 
-        let payer_bytes = &ctx.accounts.payer.key().to_bytes()[..];
+        let entry = &mut ctx.accounts.buyer_entry;
+        let new_buyer = entry.raffle == Pubkey::default() || entry.raffle_created != raffle.date_created;
 
-        let mut offset: usize = RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * raffle.tickets_purchased as usize;
+        // the entry is created on a wallet's first purchase, which is also when it becomes a unique buyer
+        // one left from an earlier raffle at this address starts over, its rent_paid is still owed by the config
+        if new_buyer {
+            entry.raffle = raffle.key();
+            entry.buyer = ctx.accounts.payer.key();
+            entry.raffle_created = raffle.date_created;
+            entry.tickets = 0;
+            entry.bump = ctx.bumps.buyer_entry;
 
-        for _x in 0..amount {
-            ticket_data[offset .. offset + 32].copy_from_slice(payer_bytes);
-            offset = offset + RAFFLE_ENTRY_SIZE;
-        };
+            raffle.unique_entries += 1;
+        }
 
-        raffle.tickets_purchased = raffle.tickets_purchased + amount;
+        // the allocation is part of the leaf, so it holds across every purchase the wallet makes
+        if raffle.allowlist != [0; 32] {
+            if !verify_allowlist(&raffle.allowlist, allowlist_leaf(ctx.accounts.payer.key, allocation), &proof) {
+                return err!(CustomError::NotAllowlisted);
+            }
 
-        let mut unique = BTreeSet::new();
-        let mut unique_entries = 0;
+            if allocation > 0 && entry.tickets + amount > allocation {
+                return err!(CustomError::WalletLimit);
+            }
+        }
 
-        for offset in (RAFFLE_ENTRY_OFFSET..raffle.tickets_purchased as usize).step_by(RAFFLE_ENTRY_SIZE) {
-            let payer_slice = &ticket_data[offset..offset + 8];
+        if raffle.max_tickets_per_wallet > 0 && entry.tickets + amount > raffle.max_tickets_per_wallet {
+            return err!(CustomError::WalletLimit);
+        }
 
-            if !unique.contains(&payer_slice) {
-                unique_entries = unique_entries + 1;
-                unique.insert(payer_slice);
+        entry.tickets += amount;
+
+        match raffle.gate {
+            TicketGate::Open => {},
+            TicketGate::Token { mint, amount } => {
+                let Some(gate_token) = &ctx.accounts.gate_token else {
+                    return err!(CustomError::GateFailed);
+                };
+
+                if gate_token.mint != mint || gate_token.amount < amount {
+                    return err!(CustomError::GateFailed);
+                }
+            },
+            TicketGate::Collection { collection } => {
+                let (Some(gate_token), Some(gate_metadata)) = (&ctx.accounts.gate_token, &ctx.accounts.gate_metadata) else {
+                    return err!(CustomError::GateFailed);
+                };
+
+                if gate_token.amount == 0 || gate_metadata.key() != metadata_address(&gate_token.mint) || gate_metadata.owner != &METADATA_PROGRAM_ID {
+                    return err!(CustomError::GateFailed);
+                }
+
+                let metadata = MintMetadata::from_account_data(&gate_metadata.data.borrow())?;
+
+                if metadata.mint != gate_token.mint || metadata.verified_collection() != Some(collection) {
+                    return err!(CustomError::GateFailed);
+                }
             }
+        }
 
-            if offset > 15000 {
-                break;
+        let payer = ctx.accounts.payer.key();
+        let mut rent_paid = 0;
+        let space = {
+            let ticket_data = ticket_account.data.borrow();
+            let runs = ticket_runs(&ticket_data);
+
+            RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * (runs.len() + needs_range(runs, &payer) as usize)
+        };
+
+        // the ticket account grows by a run whenever a new buyer comes in, the buyer pays the extra rent and gets it back through close_entry
+        if space > ticket_account.data_len() {
+            let rent = Rent::get()?.minimum_balance(space).saturating_sub(ticket_account.lamports());
+
+            rent_paid = rent;
+            entry.rent_paid += rent;
+            raffle.buyer_rent += rent;
+
+            if rent > 0 {
+                anchor_lang::system_program::transfer(
+                    CpiContext::new(ctx.accounts.system_program.to_account_info(), anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: ticket_account.clone()
+                    }),
+                    rent,
+                )?;
             }
+
+            ticket_account.realloc(space, false)?;
         }
 
-        raffle.unique_entries = unique_entries;
+        let ticket_data = &mut ticket_account.data.borrow_mut();
+        let first_ticket = raffle.tickets_purchased;
+
+        raffle.tickets_purchased += amount;
+
+        push_tickets(ticket_data, &payer, raffle.tickets_purchased);
 
         let cost_decimals = (ctx.accounts.mint_cost.decimals - raffle.cost_decimals) as u32;
+        let cost = raffle.price * 10_u64.pow(cost_decimals) * amount;
 
-        anchor_spl::token::transfer(
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::token::Transfer {
-                from: ctx.accounts.token_cost.to_account_info(),
-                to:  ctx.accounts.escrow_token_cost.to_account_info(),
-                authority:  ctx.accounts.payer.to_account_info()
-            }),
-            raffle.price * 10_u64.pow(cost_decimals) * amount,
-        )?;
+        if raffle.native {
+            let Some(vault) = &ctx.accounts.vault else {
+                return err!(CustomError::InputError);
+            };
+
+            anchor_lang::system_program::transfer(
+                CpiContext::new(ctx.accounts.system_program.to_account_info(), anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: vault.to_account_info()
+                }),
+                cost,
+            )?;
+        } else {
+            let (Some(token_cost), Some(escrow_token_cost)) = (&ctx.accounts.token_cost, &ctx.accounts.escrow_token_cost) else {
+                return err!(CustomError::InputError);
+            };
+
+            // the buyer covers any transfer fee so the escrow gets the full ticket price
+            anchor_spl::token_interface::transfer_checked(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                    from: token_cost.to_account_info(),
+                    mint: ctx.accounts.mint_cost.to_account_info(),
+                    to:  escrow_token_cost.to_account_info(),
+                    authority:  ctx.accounts.payer.to_account_info()
+                }),
+                gross_amount(&ctx.accounts.mint_cost.to_account_info(), cost)?,
+                ctx.accounts.mint_cost.decimals,
+            )?;
+        }
+
+        emit!(TicketsPurchased {
+            raffle: raffle.key(),
+            buyer: payer,
+            amount,
+            first_ticket,
+            tickets_purchased: raffle.tickets_purchased,
+            cost,
+            rent_paid,
+            new_buyer,
+            unique_entries: raffle.unique_entries,
+        });
+
+        Ok(())
+    }
+
+    // once the raffle is closed a buyer gets back the entry's rent and what they paid to grow the ticket account
+    pub fn close_entry(ctx: Context<CloseEntry>) -> Result<()> {
+        // a raffle created again at the same address doesn't hold this entry's rent, the one it was paid into is closed
+        let raffle = &ctx.accounts.raffle;
+
+        if raffle.owner == &crate::ID {
+            if let Ok(raffle) = RaffleAccount::try_deserialize(&mut &raffle.data.borrow()[..]) {
+                if raffle.date_created == ctx.accounts.buyer_entry.raffle_created {
+                    return err!(CustomError::RaffleOpen);
+                }
+            }
+        }
 
-        // vec size
-        ticket_data[RAFFLE_ENTRY_OFFSET - 4 .. RAFFLE_ENTRY_OFFSET].copy_from_slice(&raffle.tickets_purchased.to_le_bytes()[..4]);
+        let rent_paid = ctx.accounts.buyer_entry.rent_paid;
+
+        **ctx.accounts.config.to_account_info().lamports.borrow_mut() -= rent_paid;
+        **ctx.accounts.payer.to_account_info().lamports.borrow_mut() += rent_paid;
 
         Ok(())
     }
@@ -278,101 +1003,545 @@ pub mod raffler_anchor {
     pub fn draw_winner(ctx: Context<DrawWinner>) -> Result<()> {
         let raffle = &mut ctx.accounts.raffle;
         let ticket_account = ctx.accounts.fixed_raffle.to_account_info();
-        let ticket_data = &mut ticket_account.data.borrow_mut();
-
-        if ctx.accounts.fixed_raffle.owner != &ID || &ticket_data[8..40] != raffle.id.as_ref() {
-            return err!(CustomError::InputError);
-        }
+        let ticket_data = ticket_account.data.borrow();
 
         // all winners need to be set first
-        if raffle.winners_selected == false {
+        if !raffle.winners_selected {
             return err!(CustomError::InputError);
         }
 
-        if raffle.winners.len() == 0 {
+        // winners may have claimed out of order, pay the last one still waiting
+        let Some(slot) = raffle.claimed.iter().rposition(|claimed| !claimed) else {
             return err!(CustomError::AllWinnersPaid);
-        }
+        };
 
-        let winner = raffle.winners.pop().unwrap() as usize;
+        let winner = raffle.winners[slot];
+
+        if ticket_buyer(ticket_runs(&ticket_data), winner) != Some(ctx.accounts.recipient.key()) {
+            return err!(CustomError::InputError);
+        }
 
-        let offset = RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * winner;
+        // the slot decides the tier, the caller has to bring that tier's mint and escrow
+        let tier = raffle.slot_prize(slot).ok_or(CustomError::InputError)?;
 
-        if ctx.accounts.recipient.key.as_ref() != &ticket_data[offset .. offset + 32] {
+        if ctx.accounts.mint_prize.key() != tier.mint {
             return err!(CustomError::InputError);
         }
 
+        let nonce = raffle.nonce.to_le_bytes();
         let seeds: &[&[_]] = &[&[
             raffle.owner.as_ref(),
             ctx.accounts.mint_cost.to_account_info().key.as_ref(),
-            ctx.accounts.mint_prize.to_account_info().key.as_ref(),
+            raffle.prize.as_ref(),
+            &nonce,
             &[raffle.bump]
         ]];
 
-        let prize_decimals = (ctx.accounts.mint_prize.decimals - raffle.prize_decimals) as u32;
+        let prize_decimals = (ctx.accounts.mint_prize.decimals - tier.decimals) as u32;
 
-        anchor_spl::token::transfer(
-            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token::Transfer {
+        anchor_spl::token_interface::transfer_checked(
+            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
                     from: ctx.accounts.escrow_token_prize.to_account_info(),
+                    mint: ctx.accounts.mint_prize.to_account_info(),
                     to: ctx.accounts.token_prize.to_account_info(),
                     authority: raffle.to_account_info()
                 },
                 seeds
             ),
-            raffle.per_win * 10_u64.pow(prize_decimals),
+            tier.per_win * 10_u64.pow(prize_decimals),
+            ctx.accounts.mint_prize.decimals,
         )?;
 
+        raffle.claimed[slot] = true;
+
         // incremenet this so we can close a raffle just in case
-        raffle.sent_out = raffle.sent_out + 1;
+        raffle.sent_out += 1;
+
+        emit!(PrizeSent {
+            raffle: raffle.key(),
+            slot: slot as u32,
+            ticket: winner,
+            winner: ctx.accounts.recipient.key(),
+            mint: tier.mint,
+            amount: tier.per_win * 10_u64.pow(prize_decimals),
+            sent_out: raffle.sent_out,
+        });
 
         Ok(())
     }
 
-    pub fn set_winner(ctx: Context<SetWinner>) -> Result<()> {
+    pub fn claim_prize(ctx: Context<ClaimPrize>, ticket: u64) -> Result<()> {
         let raffle = &mut ctx.accounts.raffle;
         let ticket_account = ctx.accounts.fixed_raffle.to_account_info();
-        let ticket_data = &mut ticket_account.data.borrow_mut();
 
-        if ctx.accounts.fixed_raffle.owner != &ID || &ticket_data[8..40] != raffle.id.as_ref() {
+        if !raffle.winners_selected {
+            return err!(CustomError::InputError);
+        }
+
+        let mint_prize = ctx.accounts.mint_prize.key();
+        let unclaimed = |slot: &usize| raffle.winners[*slot] == ticket && !raffle.claimed[*slot];
+
+        // a ticket that won more than once claims each tier with that tier's mint
+        let Some(slot) = (0..raffle.winners.len()).filter(unclaimed).find(|slot| raffle.slot_prize(*slot).map(|tier| tier.mint) == Some(mint_prize)) else {
+            return if (0..raffle.winners.len()).any(|slot| unclaimed(&slot)) {
+                err!(CustomError::InputError)
+            } else if raffle.winners.contains(&ticket) {
+                err!(CustomError::AlreadyClaimed)
+            } else {
+                err!(CustomError::NotWinner)
+            };
+        };
+
+        let tier = raffle.slot_prize(slot).ok_or(CustomError::InputError)?;
+
+        if ticket_buyer(ticket_runs(&ticket_account.data.borrow()), ticket) != Some(ctx.accounts.winner.key()) {
+            return err!(CustomError::NotWinner);
+        }
+
+        if ctx.accounts.token_prize.key() != anchor_spl::associated_token::get_associated_token_address_with_program_id(ctx.accounts.winner.key, &mint_prize, ctx.accounts.token_program.key) {
             return err!(CustomError::InputError);
         }
 
+        if ctx.accounts.token_prize.to_account_info().data_is_empty() {
+            anchor_spl::associated_token::create(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::associated_token::Create {
+                    payer: ctx.accounts.payer.to_account_info(),
+                    associated_token: ctx.accounts.token_prize.to_account_info(),
+                    authority: ctx.accounts.winner.to_account_info(),
+                    mint: ctx.accounts.mint_prize.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info()
+                }),
+            )?;
+        }
+
+        let nonce = raffle.nonce.to_le_bytes();
+        let seeds: &[&[_]] = &[&[
+            raffle.owner.as_ref(),
+            raffle.mint.as_ref(),
+            raffle.prize.as_ref(),
+            &nonce,
+            &[raffle.bump]
+        ]];
+
+        let prize_decimals = (ctx.accounts.mint_prize.decimals - tier.decimals) as u32;
+
+        anchor_spl::token_interface::transfer_checked(
+            CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                    from: ctx.accounts.escrow_token_prize.to_account_info(),
+                    mint: ctx.accounts.mint_prize.to_account_info(),
+                    to: ctx.accounts.token_prize.to_account_info(),
+                    authority: raffle.to_account_info()
+                },
+                seeds
+            ),
+            tier.per_win * 10_u64.pow(prize_decimals),
+            ctx.accounts.mint_prize.decimals,
+        )?;
+
+        raffle.claimed[slot] = true;
+        raffle.sent_out += 1;
+
+        emit!(PrizeSent {
+            raffle: raffle.key(),
+            slot: slot as u32,
+            ticket,
+            winner: ctx.accounts.winner.key(),
+            mint: tier.mint,
+            amount: tier.per_win * 10_u64.pow(prize_decimals),
+            sent_out: raffle.sent_out,
+        });
+
+        Ok(())
+    }
+
+    pub fn lock_raffle(ctx: Context<LockRaffle>) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
         let clock = Clock::get()?;
 
         if clock.unix_timestamp < raffle.end && raffle.max_entries > raffle.tickets_purchased {
             return err!(CustomError::RaffleGoing);
         }
 
-        let slot_hashes = &ctx.accounts.slot_hashes;
+        if raffle.locked_at != 0 {
+            return err!(CustomError::AlreadyLocked);
+        }
+
+        if raffle.refunding {
+            return err!(CustomError::RaffleCancelled);
+        }
+
+        // undersold raffles don't get drawn, they go to refunds through cancel_raffle
+        if raffle.undersold() {
+            return err!(CustomError::MinTicketsNotReached);
+        }
 
-        if slot_hashes.key().to_string() != "SysvarS1otHashes111111111111111111111111111" || raffle.tickets_purchased == 0 {
+        if raffle.tickets_purchased == 0 {
             return err!(CustomError::InputError);
         }
 
-        let random = u64::from_le_bytes(slot_hashes.to_account_info().data.borrow()[16..24].try_into().unwrap());
-        let winner: usize = random.checked_rem(raffle.tickets_purchased).unwrap() as usize;
+        // only the slot, every hash up to it is already public and the creator would know the outcome before locking
+        raffle.lock_slot = clock.slot;
+        raffle.locked_at = clock.unix_timestamp;
+
+        emit!(RaffleLocked {
+            raffle: raffle.key(),
+            locked_by: ctx.accounts.payer.key(),
+            lock_slot: raffle.lock_slot,
+            locked_at: raffle.locked_at,
+            tickets_purchased: raffle.tickets_purchased,
+        });
+
+        Ok(())
+    }
+
+    // SlotHashes only goes back MAX_ENTRIES slots, keeping the hash on the raffle gives the reveal all of REVEAL_TIMEOUT
+    pub fn record_slot_hash(ctx: Context<RecordSlotHash>) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
+
+        if raffle.locked_at == 0 {
+            return err!(CustomError::NotLocked);
+        }
+
+        if raffle.source != RandomnessSource::SlotHash || raffle.slot_hash != [0; 32] {
+            return err!(CustomError::InputError);
+        }
+
+        if raffle.refunding {
+            return err!(CustomError::RaffleCancelled);
+        }
+
+        let slot_hashes = ctx.accounts.slot_hashes.to_account_info();
+        let Some(slot_hash) = slot_hash_at(&slot_hashes.data.borrow(), raffle.lock_slot + ENTROPY_DELAY)? else {
+            return err!(CustomError::RevealPending);
+        };
+
+        raffle.slot_hash = slot_hash;
+
+        Ok(())
+    }
+
+    pub fn reveal_secret(ctx: Context<RevealSecret>, secret: [u8; 32]) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
+
+        if raffle.locked_at == 0 {
+            return err!(CustomError::NotLocked);
+        }
+
+        if raffle.revealed || raffle.source != RandomnessSource::SlotHash {
+            return err!(CustomError::InputError);
+        }
+
+        if raffle.refunding {
+            return err!(CustomError::RaffleCancelled);
+        }
+
+        if commitment(&secret) != raffle.commitment {
+            return err!(CustomError::BadReveal);
+        }
+
+        // the entry only sticks around for MAX_ENTRIES slots, unless it was recorded a reveal that misses it leaves the raffle to refund
+        if raffle.slot_hash == [0; 32] {
+            let slot_hashes = ctx.accounts.slot_hashes.to_account_info();
+            let Some(slot_hash) = slot_hash_at(&slot_hashes.data.borrow(), raffle.lock_slot + ENTROPY_DELAY)? else {
+                return err!(CustomError::RevealPending);
+            };
+
+            raffle.slot_hash = slot_hash;
+        }
+
+        raffle.seed = reveal_seed(&secret, &raffle.slot_hash);
+        raffle.revealed = true;
+
+        emit!(SeedRevealed {
+            raffle: raffle.key(),
+            source: raffle.source,
+            slot_hash: raffle.slot_hash,
+            seed: raffle.seed,
+        });
+
+        Ok(())
+    }
+
+    pub fn request_randomness(ctx: Context<RequestRandomness>) -> Result<()> {
+        let raffle = &ctx.accounts.raffle;
+
+        if raffle.locked_at == 0 {
+            return err!(CustomError::NotLocked);
+        }
+
+        let request = &mut ctx.accounts.request;
+        request.raffle = raffle.id;
+        request.oracle = raffle.oracle;
+        request.alpha = oracle_alpha(&raffle.id, raffle.lock_slot);
+        request.fulfilled = false;
+        request.bump = ctx.bumps.request;
+
+        emit!(RandomnessRequested {
+            raffle: raffle.key(),
+            request: request.key(),
+            oracle: request.oracle,
+            alpha: request.alpha,
+        });
+
+        Ok(())
+    }
+
+    pub fn fulfill_randomness(ctx: Context<FulfillRandomness>, proof: [u8; 64]) -> Result<()> {
+        let request = &mut ctx.accounts.request;
+
+        if request.fulfilled {
+            return err!(CustomError::InputError);
+        }
+
+        // the ed25519 program instruction right before this one has to carry the proof
+        let instructions = ctx.accounts.instructions.to_account_info();
+        let current = load_current_index_checked(&instructions)? as usize;
+
+        if current == 0 {
+            return err!(CustomError::BadProof);
+        }
+
+        let ix = load_instruction_at_checked(current - 1, &instructions)?;
+
+        if !verify_oracle_proof(&ix, &request.oracle, &request.alpha, &proof) {
+            return err!(CustomError::BadProof);
+        }
+
+        request.output = oracle_output(&proof);
+        request.fulfilled = true;
+        request.fulfilled_slot = Clock::get()?.slot;
+
+        emit!(RandomnessFulfilled {
+            raffle: request.raffle,
+            request: request.key(),
+            output: request.output,
+            fulfilled_slot: request.fulfilled_slot,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_winner(ctx: Context<SetWinner>) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
 
         // we have reached a max # of winners and can not set anymore
         if raffle.winners_selected {
             return err!(CustomError::WinnersAlreadyPicked);
         }
 
-        let offset = RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * winner;
+        if raffle.locked_at == 0 {
+            return err!(CustomError::NotLocked);
+        }
 
-        // this entry has 'won' already and can't win multiple times
-        if ticket_data[offset + 32] >= 1 && !raffle.win_multiple {
-            return err!(CustomError::InputError);
+        if raffle.refunding {
+            return err!(CustomError::RaffleCancelled);
+        }
+
+        let clock = Clock::get()?;
+
+        if !raffle.revealed {
+            let seed = match raffle.source {
+                RandomnessSource::SlotHash => resolve_seed(&SlotHashProvider, raffle, clock.unix_timestamp)?,
+                RandomnessSource::Oracle => {
+                    let provider = OracleProvider::load(
+                        raffle,
+                        &ctx.accounts.randomness_request.to_account_info(),
+                        &ctx.accounts.slot_hashes.to_account_info(),
+                    )?;
+
+                    raffle.slot_hash = provider.slot_hash.unwrap_or_default();
+                    resolve_seed(&provider, raffle, clock.unix_timestamp)?
+                }
+            };
+
+            // nothing came in time, withholding the randomness can only ever cancel the raffle
+            let Some(seed) = seed else {
+                raffle.refunding = true;
+
+                emit!(RaffleCancelled {
+                    raffle: raffle.key(),
+                    cancelled_by: ctx.accounts.payer.key(),
+                    undersold: false,
+                    timed_out: true,
+                    tickets_purchased: raffle.tickets_purchased,
+                });
+
+                return Ok(());
+            };
+
+            raffle.seed = seed;
+            raffle.revealed = true;
+
+            emit!(SeedRevealed {
+                raffle: raffle.key(),
+                source: raffle.source,
+                slot_hash: raffle.slot_hash,
+                seed,
+            });
+        }
+
+        let mut winner = None;
+
+        for _ in 0..MAX_DRAW_ATTEMPTS {
+            let index = winning_index(&raffle.seed, raffle.draw_nonce, raffle.tickets_purchased);
+            raffle.draw_nonce += 1;
+
+            if let Some(index) = index {
+                // this ticket has 'won' already and can't win multiple times
+                if raffle.winners.contains(&index) && !raffle.win_multiple {
+                    continue;
+                }
+
+                winner = Some(index);
+                break;
+            }
         }
 
-        // we can count wins I guess up to 255? not an issue (?)
-        ticket_data[offset + 32] = ticket_data[offset + 32] + 1;
+        // nothing usable this round, the nonce moved on so the next call draws fresh
+        let Some(winner) = winner else {
+            return Ok(());
+        };
 
-        raffle.winners.push(winner as u64);
+        raffle.winners.push(winner);
+        raffle.claimed.push(false);
 
         // all winners have been picked, we can now pay them out
-        if raffle.winners.len() == (raffle.prize_quantity / raffle.per_win) as usize {
+        if raffle.winners.len() == raffle.winner_count() {
             raffle.winners_selected = true;
         }
 
+        let slot = raffle.winners.len() - 1;
+        let ticket_account = ctx.accounts.fixed_raffle.to_account_info();
+
+        emit!(WinnerSelected {
+            raffle: raffle.key(),
+            slot: slot as u32,
+            ticket: winner,
+            buyer: ticket_buyer(ticket_runs(&ticket_account.data.borrow()), winner).unwrap_or_default(),
+            mint: raffle.slot_prize(slot).map(|tier| tier.mint).unwrap_or_default(),
+            seed: raffle.seed,
+            // the winning draw already moved the nonce on
+            draw_nonce: raffle.draw_nonce - 1,
+            tickets_purchased: raffle.tickets_purchased,
+            winners_selected: raffle.winners_selected,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_raffle(ctx: Context<CancelRaffle>) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
+        let payer = ctx.accounts.payer.key;
+        let clock = Clock::get()?;
+
+        if raffle.refunding {
+            return err!(CustomError::RaffleCancelled);
+        }
+
+        if raffle.winners_selected {
+            return err!(CustomError::WinnersAlreadyPicked);
+        }
+
+        let undersold = clock.unix_timestamp > raffle.end && raffle.undersold();
+        let is_admin = *payer == ctx.accounts.config.admin;
+
+        // the creator can back out until the raffle is locked, after that they could already know the outcome
+        let is_creator = *payer == raffle.owner && raffle.locked_at == 0;
+
+        if !undersold && !is_creator && !is_admin {
+            return err!(CustomError::InputError);
+        }
+
+        raffle.refunding = true;
+
+        emit!(RaffleCancelled {
+            raffle: raffle.key(),
+            cancelled_by: *payer,
+            undersold,
+            timed_out: false,
+            tickets_purchased: raffle.tickets_purchased,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_refund(ctx: Context<ClaimRefund>) -> Result<()> {
+        let raffle = &mut ctx.accounts.raffle;
+        let entry = &mut ctx.accounts.buyer_entry;
+
+        if !raffle.refunding {
+            return err!(CustomError::NotCancelled);
+        }
+
+        // an entry left over from an earlier raffle at this address holds nothing here
+        let tickets_held = if entry.raffle_created == raffle.date_created { entry.tickets } else { 0 };
+
+        if tickets_held == 0 {
+            return err!(CustomError::NothingToRefund);
+        }
+
+        // zeroed so it can't be claimed twice, rent_paid stays for close_entry
+        entry.tickets = 0;
+
+        raffle.tickets_refunded += tickets_held;
+
+        let nonce = raffle.nonce.to_le_bytes();
+        let seeds: &[&[_]] = &[&[
+            raffle.owner.as_ref(),
+            raffle.mint.as_ref(),
+            raffle.prize.as_ref(),
+            &nonce,
+            &[raffle.bump]
+        ]];
+
+        let cost_decimals = (ctx.accounts.mint_cost.decimals - raffle.cost_decimals) as u32;
+        let refund = raffle.price * 10_u64.pow(cost_decimals) * tickets_held;
+
+        if raffle.native {
+            let Some(vault) = &ctx.accounts.vault else {
+                return err!(CustomError::InputError);
+            };
+
+            let raffle_key = raffle.key();
+            let vault_seeds: &[&[_]] = &[&[VAULT_SEED, raffle_key.as_ref(), &[raffle.vault_bump]]];
+
+            anchor_lang::system_program::transfer(
+                CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), anchor_lang::system_program::Transfer {
+                        from: vault.to_account_info(),
+                        to: ctx.accounts.payer.to_account_info()
+                    },
+                    vault_seeds
+                ),
+                refund,
+            )?;
+        } else {
+            let (Some(token_cost), Some(escrow_token_cost)) = (&ctx.accounts.token_cost, &ctx.accounts.escrow_token_cost) else {
+                return err!(CustomError::InputError);
+            };
+
+            anchor_spl::token_interface::transfer_checked(
+                CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), anchor_spl::token_interface::TransferChecked {
+                        from: escrow_token_cost.to_account_info(),
+                        mint: ctx.accounts.mint_cost.to_account_info(),
+                        to: token_cost.to_account_info(),
+                        authority: raffle.to_account_info()
+                    },
+                    seeds
+                ),
+                refund,
+                ctx.accounts.mint_cost.decimals,
+            )?;
+        }
+
+        emit!(RefundClaimed {
+            raffle: raffle.key(),
+            buyer: ctx.accounts.payer.key(),
+            tickets: tickets_held,
+            amount: refund,
+            tickets_refunded: raffle.tickets_refunded,
+        });
+
         Ok(())
     }
 
@@ -389,8 +1558,7 @@ pub mod raffler_anchor {
                     authority: ctx.accounts.raffle.to_account_info(),
                     mint: ctx.accounts.mint_prize.to_account_info(),
                     system_program: ctx.accounts.system_program.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info(),
-                    rent: ctx.accounts.rent.to_account_info()
+                    token_program: ctx.accounts.token_program.to_account_info()
                 }),
             )?;
         }
@@ -403,36 +1571,33 @@ pub mod raffler_anchor {
                     authority: ctx.accounts.recipient.to_account_info(),
                     mint: ctx.accounts.mint_prize.to_account_info(),
                     system_program: ctx.accounts.system_program.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info(),
-                    rent: ctx.accounts.rent.to_account_info()
+                    token_program: ctx.accounts.token_program.to_account_info()
                 }),
             )?;
         }
 
         if ctx.accounts.escrow_token_cost.to_account_info().data.borrow().len() == 0 {
             anchor_spl::associated_token::create(
-                CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::associated_token::Create {
+                CpiContext::new(ctx.accounts.cost_token_program.to_account_info(), anchor_spl::associated_token::Create {
                     payer: ctx.accounts.payer.to_account_info(),
                     associated_token: ctx.accounts.escrow_token_cost.to_account_info(),
                     authority: ctx.accounts.raffle.to_account_info(),
                     mint: ctx.accounts.mint_cost.to_account_info(),
                     system_program: ctx.accounts.system_program.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info(),
-                    rent: ctx.accounts.rent.to_account_info()
+                    token_program: ctx.accounts.cost_token_program.to_account_info()
                 }),
             )?;
         }
 
         if ctx.accounts.token_cost.to_account_info().data.borrow().len() == 0 {
             anchor_spl::associated_token::create(
-                CpiContext::new(ctx.accounts.token_program.to_account_info(), anchor_spl::associated_token::Create {
+                CpiContext::new(ctx.accounts.cost_token_program.to_account_info(), anchor_spl::associated_token::Create {
                     payer: ctx.accounts.payer.to_account_info(),
                     associated_token: ctx.accounts.token_cost.to_account_info(),
                     authority: ctx.accounts.recipient.to_account_info(),
                     mint: ctx.accounts.mint_cost.to_account_info(),
                     system_program: ctx.accounts.system_program.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info(),
-                    rent: ctx.accounts.rent.to_account_info()
+                    token_program: ctx.accounts.cost_token_program.to_account_info()
                 }),
             )?;
         }
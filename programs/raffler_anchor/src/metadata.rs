@@ -0,0 +1,70 @@
+use {
+    anchor_lang::prelude::*,
+    crate::error::*,
+};
+
+pub mod token_metadata {
+    anchor_lang::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+pub const METADATA_PROGRAM_ID: Pubkey = token_metadata::ID;
+
+// PDA of < "metadata" - metadata program - mint > under the metadata program
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"metadata", METADATA_PROGRAM_ID.as_ref(), mint.as_ref()], &METADATA_PROGRAM_ID).0
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
+pub struct MetadataCreator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
+pub struct MetadataCollection {
+    pub verified: bool,
+    pub key: Pubkey,
+}
+
+// the leading fields of a metaplex metadata account, everything after the collection is left unread
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
+pub struct MintMetadata {
+    pub key: u8,
+    pub update_authority: Pubkey,
+    pub mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub seller_fee_basis_points: u16,
+    pub creators: Option<Vec<MetadataCreator>>,
+    pub primary_sale_happened: bool,
+    pub is_mutable: bool,
+    pub edition_nonce: Option<u8>,
+    pub token_standard: Option<u8>,
+    pub collection: Option<MetadataCollection>,
+}
+
+impl MintMetadata {
+    // MetadataV1
+    pub const KEY: u8 = 4;
+
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let metadata = Self::deserialize(&mut &data[..]).map_err(|_| error!(CustomError::MetadataMismatch))?;
+
+        if metadata.key != Self::KEY {
+            return err!(CustomError::MetadataMismatch);
+        }
+
+        Ok(metadata)
+    }
+
+    // strings are stored padded out to their max length with nulls
+    pub fn uri(&self) -> &str {
+        self.uri.trim_end_matches('\0')
+    }
+
+    pub fn verified_collection(&self) -> Option<Pubkey> {
+        self.collection.as_ref().filter(|collection| collection.verified).map(|collection| collection.key)
+    }
+}
@@ -0,0 +1,179 @@
+use {
+    anchor_lang::prelude::*,
+    anchor_lang::solana_program::{
+        ed25519_program,
+        hash::{hash, hashv},
+        instruction::Instruction,
+        slot_hashes::MAX_ENTRIES,
+    },
+    crate::{error::*, state::*},
+};
+
+// how long the source has to deliver after the raffle is locked, after that anyone can send it to refunds
+pub const REVEAL_TIMEOUT: i64 = 60 * 60 * 24;
+
+// the seed mixes in the hash of the first slot this far past the lock, nobody can know it when the lock lands
+pub const ENTROPY_DELAY: u64 = 4;
+
+// hashes to try per set_winner call, rejected draws still advance the nonce so the next call picks up from there
+pub const MAX_DRAW_ATTEMPTS: u8 = 8;
+
+// where a raffle's seed comes from, picked per raffle by RaffleAccount.source
+pub trait RandomnessProvider {
+    // None until the source has delivered
+    fn seed(&self, raffle: &RaffleAccount) -> Option<[u8; 32]>;
+}
+
+// commit-reveal, the creator's secret mixed with the hash of a slot after the lock
+pub struct SlotHashProvider;
+
+impl RandomnessProvider for SlotHashProvider {
+    fn seed(&self, raffle: &RaffleAccount) -> Option<[u8; 32]> {
+        if raffle.revealed {
+            Some(raffle.seed)
+        } else {
+            None
+        }
+    }
+}
+
+// an oracle signs the request alpha, the signature's hash is mixed with the hash of a slot after the fulfilment
+pub struct OracleProvider {
+    request: RandomnessRequest,
+    // None until that slot has passed, and for good once it's out of SlotHashes
+    pub slot_hash: Option<[u8; 32]>,
+}
+
+impl OracleProvider {
+    pub fn load(raffle: &RaffleAccount, info: &AccountInfo, slot_hashes: &AccountInfo) -> Result<Self> {
+        if info.owner != &crate::ID {
+            return err!(CustomError::InputError);
+        }
+
+        let request = RandomnessRequest::try_deserialize(&mut &info.data.borrow()[..])?;
+
+        if request.raffle != raffle.id {
+            return err!(CustomError::InputError);
+        }
+
+        // an expired slot hash never delivers, the raffle refunds at the timeout like any other missing seed
+        let slot_hash = if request.fulfilled {
+            slot_hash_at(&slot_hashes.data.borrow(), request.fulfilled_slot + ENTROPY_DELAY).unwrap_or(None)
+        } else {
+            None
+        };
+
+        Ok(OracleProvider { request, slot_hash })
+    }
+}
+
+impl RandomnessProvider for OracleProvider {
+    fn seed(&self, _raffle: &RaffleAccount) -> Option<[u8; 32]> {
+        self.slot_hash.map(|slot_hash| oracle_seed(&self.request.output, &slot_hash))
+    }
+}
+
+// the source gets REVEAL_TIMEOUT to deliver, None after that and the raffle refunds
+// there's no fallback seed, anything fixed by then is known to whoever chose not to deliver
+pub fn resolve_seed(provider: &dyn RandomnessProvider, raffle: &RaffleAccount, now: i64) -> Result<Option<[u8; 32]>> {
+    if let Some(seed) = provider.seed(raffle) {
+        return Ok(Some(seed));
+    }
+
+    if now < raffle.locked_at + REVEAL_TIMEOUT {
+        return err!(CustomError::RevealPending);
+    }
+
+    Ok(None)
+}
+
+// the hash of the first slot at or after `target` in the SlotHashes sysvar data, skipped slots have no entry
+// None until that slot has passed, EntropyExpired once it's older than every entry
+pub fn slot_hash_at(data: &[u8], target: u64) -> Result<Option<[u8; 32]>> {
+    let count = data.get(..8).map_or(0, |len| u64::from_le_bytes(len.try_into().unwrap())) as usize;
+    let mut found = None;
+
+    // newest first, so the last entry at or after `target` is the one right after it
+    for entry in data.get(8..).unwrap_or_default().chunks_exact(40).take(count) {
+        if u64::from_le_bytes(entry[..8].try_into().unwrap()) < target {
+            return Ok(found);
+        }
+
+        found = Some(entry[8..].try_into().unwrap());
+    }
+
+    // nothing older than `target` left to compare with, a full sysvar may have dropped the right entry already
+    if found.is_some() && count >= MAX_ENTRIES {
+        return err!(CustomError::EntropyExpired);
+    }
+
+    Ok(found)
+}
+
+// what the creator hands in at create_raffle
+pub fn commitment(secret: &[u8; 32]) -> [u8; 32] {
+    hash(secret).to_bytes()
+}
+
+// the creator's secret is mixed with the hash of a slot after the lock, neither side picks the result alone
+pub fn reveal_seed(secret: &[u8; 32], slot_hash: &[u8; 32]) -> [u8; 32] {
+    hashv(&[b"reveal", secret, slot_hash]).to_bytes()
+}
+
+// what the oracle signs, it only exists once the raffle is locked
+pub fn oracle_alpha(raffle: &Pubkey, lock_slot: u64) -> [u8; 32] {
+    hashv(&[b"alpha", raffle.as_ref(), &lock_slot.to_le_bytes()]).to_bytes()
+}
+
+// an ed25519 signature isn't a vrf, the oracle can make any number of valid ones for an alpha
+// so the output alone isn't the seed, see oracle_seed
+pub fn oracle_output(proof: &[u8; 64]) -> [u8; 32] {
+    hashv(&[b"vrf", proof]).to_bytes()
+}
+
+// the output is on chain before the slot is, so whichever signature the oracle picked it couldn't see the result
+pub fn oracle_seed(output: &[u8; 32], slot_hash: &[u8; 32]) -> [u8; 32] {
+    hashv(&[b"oracle", output, slot_hash]).to_bytes()
+}
+
+// checks an ed25519 program instruction carrying exactly one signature of `message` by `oracle`
+pub fn verify_oracle_proof(ix: &Instruction, oracle: &Pubkey, message: &[u8], proof: &[u8; 64]) -> bool {
+    if ix.program_id != ed25519_program::ID || ix.data.len() < 16 || ix.data[0] != 1 {
+        return false;
+    }
+
+    let read = |at: usize| u16::from_le_bytes([ix.data[at], ix.data[at + 1]]) as usize;
+
+    let signature_offset = read(2);
+    let public_key_offset = read(6);
+    let message_offset = read(10);
+    let message_size = read(12);
+
+    // everything has to live in the ed25519 instruction itself
+    if read(4) != u16::MAX as usize || read(8) != u16::MAX as usize || read(14) != u16::MAX as usize {
+        return false;
+    }
+
+    let slice = |offset: usize, len: usize| ix.data.get(offset..offset + len);
+
+    slice(public_key_offset, 32) == Some(oracle.as_ref())
+        && slice(signature_offset, 64) == Some(&proof[..])
+        && slice(message_offset, message_size) == Some(message)
+}
+
+// rejection sampling, a plain modulo would favor the low ticket indices
+pub fn winning_index(seed: &[u8; 32], nonce: u64, tickets: u64) -> Option<u64> {
+    if tickets == 0 {
+        return None;
+    }
+
+    let digest = hashv(&[seed, &nonce.to_le_bytes()]).to_bytes();
+    let random = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    let zone = u64::MAX - u64::MAX % tickets;
+
+    if random >= zone {
+        return None;
+    }
+
+    Some(random % tickets)
+}
@@ -1,22 +1,81 @@
 use {
     anchor_lang::*,
     anchor_lang::prelude::*,
-    anchor_spl::token::{Token, TokenAccount, Mint},
-    anchor_spl::associated_token::{AssociatedToken}
+    anchor_spl::token_interface::{TokenInterface, TokenAccount, Mint},
+    anchor_spl::associated_token::{AssociatedToken},
+    crate::{program::RafflerAnchor, error::CustomError},
+    std::ops::Range
 };
 
-pub const MOON_KEY: &str = "MoonJpLwzBSu2SEeXe42rDySA21NifCuPBDPr5jExET";
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const ENTRY_SEED: &[u8] = b"entry";
+pub const MAX_BPS: u16 = 10_000;
 
-pub const RAFFLE_ENTRY_OFFSET: usize = 8 + 32 + 4;
-pub const RAFFLE_ENTRY_SIZE: usize = 33;
+// the main prize plus up to three extra tiers
+pub const MAX_PRIZE_TIERS: usize = 4;
 
+// distinct nfts a raffle can hand out on top of its tiers
+pub const MAX_BUNDLE_NFTS: usize = 10;
+
+// raffles are created at 1200 bytes and grow as prizes are added, create_raffle can't grow one past this in a single go
+pub const MAX_RAFFLE_SPACE: usize = anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+
+pub const RAFFLE_ENTRY_OFFSET: usize = 8 + std::mem::size_of::<FixedTicketAccount>();
+pub const RAFFLE_ENTRY_SIZE: usize = std::mem::size_of::<FixedEntry>();
+
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 2 + 2 + 2 + 1 + 1,
+        seeds = [CONFIG_SEED], bump,
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key())
+    )]
+    pub program: Program<'info, RafflerAnchor>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(payer.key())
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], bump = config.bump,
+        constraint = config.admin == *payer.key
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetRaffleFee<'info> {
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    #[account(
+        seeds = [CONFIG_SEED], bump = config.bump,
+        constraint = config.admin == *payer.key
+    )]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+}
 
 #[derive(Accounts)]
 pub struct InitTokenAccounts<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
-    pub mint_cost: Account<'info, Mint>,
-    pub mint_prize: Account<'info, Mint>,
+    pub mint_cost: InterfaceAccount<'info, Mint>,
+    pub mint_prize: InterfaceAccount<'info, Mint>,
     #[account(mut)]
     /// CHECK: yeah
     pub token_prize: UncheckedAccount<'info>,
@@ -31,8 +90,9 @@ pub struct InitTokenAccounts<'info> {
     pub escrow_token_cost: UncheckedAccount<'info>,
     pub associated_token: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // the cost mint can live under the other token program
+    pub cost_token_program: Interface<'info, TokenInterface>,
     /// CHECK: yeah
     pub raffle: UncheckedAccount<'info>,
     /// CHECK: yeah
@@ -40,103 +100,226 @@ pub struct InitTokenAccounts<'info> {
 }
 
 #[derive(Accounts)]
+#[instruction(data: CreateRaffleData)]
 pub struct CreateRaffle<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
-    pub mint_cost: Account<'info, Mint>,
+    pub mint_cost: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         constraint = payer.key == &token_prize.owner,
         constraint = mint_prize.key() == token_prize.mint
     )]
-    pub token_prize: Account<'info, TokenAccount>,
-    pub mint_prize: Account<'info, Mint>,
+    pub token_prize: InterfaceAccount<'info, TokenAccount>,
+    pub mint_prize: InterfaceAccount<'info, Mint>,
     #[account(
         init,
         payer = payer,
-        space = 1000,
-        seeds = [payer.key().as_ref(), mint_cost.key().as_ref(), mint_prize.key().as_ref()], bump,
+        space = 1200,
+        seeds = [payer.key().as_ref(), mint_cost.key().as_ref(), mint_prize.key().as_ref(), &data.nonce.to_le_bytes()], bump,
     )]
     pub raffle: Box<Account<'info, RaffleAccount>>,
     #[account(
         zero
     )]
-    pub fixed_raffle: Box<Account<'info, FixedTicketAccount>>,
+    pub fixed_raffle: AccountLoader<'info, FixedTicketAccount>,
+    #[account(
+        seeds = [CONFIG_SEED], bump = config.bump
+    )]
+    pub config: Box<Account<'info, ProtocolConfig>>,
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     #[account(
         mut,
         constraint = raffle.key() == escrow_token_prize.owner,
         constraint = escrow_token_prize.mint == mint_prize.key()
     )]
-    pub escrow_token_prize: Account<'info, TokenAccount>
+    pub escrow_token_prize: InterfaceAccount<'info, TokenAccount>,
+    // native raffles only
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, raffle.key().as_ref()], bump
+    )]
+    pub vault: Option<SystemAccount<'info>>,
+    /// CHECK: the prize's metaplex metadata, only read with verify_metadata
+    pub prize_metadata: Option<UncheckedAccount<'info>>,
 }
 
+#[derive(Accounts)]
+pub struct AddPrizeTier<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = raffle.owner == *payer.key
+    )]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    pub mint_prize: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = payer.key == &token_prize.owner,
+        constraint = mint_prize.key() == token_prize.mint
+    )]
+    pub token_prize: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    /// CHECK: the raffle's ATA for this tier's mint, created if it doesn't exist yet
+    pub escrow_token_prize: UncheckedAccount<'info>,
+    pub associated_token: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// the nfts come in as remaining accounts, [mint, creator token account, raffle escrow] for each
+#[derive(Accounts)]
+pub struct AddBundle<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = raffle.owner == *payer.key,
+        constraint = raffle.prize == mint_prize.key()
+    )]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    #[account(
+        mint::token_program = token_program
+    )]
+    pub mint_prize: Box<InterfaceAccount<'info, Mint>>,
+    pub associated_token: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePrizeTier<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = raffle.owner == *payer.key || payer.key() == config.admin
+    )]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    #[account(mut)]
+    pub mint_prize: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = payer.key == &token_prize.owner || payer.key() == config.admin,
+        constraint = mint_prize.key() == token_prize.mint
+    )]
+    pub token_prize: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = raffle.key() == escrow_token_prize.owner,
+        constraint = escrow_token_prize.mint == mint_prize.key()
+    )]
+    pub escrow_token_prize: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = fee_recipient.key() == config.fee_recipient,
+    )]
+    pub fee_recipient: SystemAccount<'info>,
+    #[account(
+        seeds = [CONFIG_SEED], bump = config.bump
+    )]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// bundle nfts come in as remaining accounts, [mint, creator token account, raffle escrow] for each like add_bundle
 #[derive(Accounts)]
 pub struct CloseRaffle<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(mut)]
-    pub mint_cost: Account<'info, Mint>,
+    pub mint_cost: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
-        constraint = payer.key == &token_prize.owner || payer.key.to_string() == MOON_KEY,
+        constraint = raffle.owner == token_prize.owner,
         constraint = mint_prize.key() == token_prize.mint
     )]
-    pub token_prize: Account<'info, TokenAccount>,
+    pub token_prize: InterfaceAccount<'info, TokenAccount>,
+    // spl raffles only
     #[account(
         mut,
-        constraint = payer.key == &token_cost.owner || payer.key.to_string() == MOON_KEY,
+        constraint = raffle.owner == token_cost.owner,
         constraint = mint_cost.key() == token_cost.mint
     )]
-    pub token_cost: Box<Account<'info, TokenAccount>>,
-    pub mint_prize: Box<Account<'info, Mint>>,
+    pub token_cost: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut)]
+    pub mint_prize: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
-        constraint = raffle.owner == *payer.key || payer.key.to_string() == MOON_KEY,
+        constraint = raffle.owner == *payer.key || payer.key() == config.admin,
         constraint = raffle.mint == mint_cost.key(),
         constraint = raffle.prize == mint_prize.key()
     )]
     pub raffle: Box<Account<'info, RaffleAccount>>,
+    // the creator, native proceeds and the ticket account's rent go back to them even when the admin closes
+    #[account(
+        mut,
+        address = raffle.owner
+    )]
+    pub owner: SystemAccount<'info>,
     #[account(
         mut,
-//        constraint = fixed_raffle.raffle_id == raffle.key()
+        constraint = fixed_raffle.load()?.raffle_id == raffle.key() @ CustomError::InputError
     )]
-    /// CHECK: see constraint
-    pub fixed_raffle: UncheckedAccount<'info>, // FixedTicketAccount
+    pub fixed_raffle: AccountLoader<'info, FixedTicketAccount>,
     pub system_program: Program<'info, System>,
-    pub token_program:  Program<'info, Token>,
+    pub token_program:  Interface<'info, TokenInterface>,
+    // the cost mint can live under the other token program
+    pub cost_token_program: Interface<'info, TokenInterface>,
     #[account(
         mut,
         constraint = raffle.key() == escrow_token_prize.owner,
         constraint = escrow_token_prize.mint == mint_prize.key()
     )]
-    pub escrow_token_prize: Box<Account<'info, TokenAccount>>,
+    pub escrow_token_prize: Box<InterfaceAccount<'info, TokenAccount>>,
+    // spl raffles only
     #[account(
         mut,
         constraint = raffle.key() == escrow_token_cost.owner,
         constraint = escrow_token_cost.mint == mint_cost.key()
     )]
-    pub escrow_token_cost: Box<Account<'info, TokenAccount>>,
+    pub escrow_token_cost: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        constraint = fee_recipient.key() == config.fee_recipient,
+    )]
+    pub fee_recipient: SystemAccount<'info>,
+    // spl raffles only
+    #[account(
+        mut,
+        constraint = fee_token_cost.owner == config.fee_recipient,
+        constraint = fee_token_cost.mint == mint_cost.key()
+    )]
+    pub fee_token_cost: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    // holds the buyers' ticket rent until they close their entries
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], bump = config.bump
+    )]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+    // native raffles only
     #[account(
         mut,
-        constraint = moon.key().to_string() == MOON_KEY,
+        seeds = [VAULT_SEED, raffle.key().as_ref()], bump = raffle.vault_bump
     )]
-    pub moon: SystemAccount<'info>,
+    pub vault: Option<SystemAccount<'info>>,
 }
 
 #[derive(Accounts)]
 pub struct BuyTicket<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
-    pub mint_cost: Account<'info, Mint>,
+    pub mint_cost: InterfaceAccount<'info, Mint>,
+    // spl raffles only
     #[account(
         mut,
         constraint = payer.key == &token_cost.owner,
         constraint = mint_cost.key() == token_cost.mint
     )]
-    pub token_cost: Account<'info, TokenAccount>,
-    pub mint_prize: Account<'info, Mint>,
+    pub token_cost: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub mint_prize: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         constraint = raffle.mint == mint_cost.key(),
@@ -145,19 +328,61 @@ pub struct BuyTicket<'info> {
     pub raffle: Box<Account<'info, RaffleAccount>>,
     #[account(
         mut,
-//        constraint = fixed_raffle.raffle_id == raffle.key()
+        constraint = fixed_raffle.load()?.raffle_id == raffle.key() @ CustomError::InputError
     )]
-    /// CHECK: see constraint
-    pub fixed_raffle: UncheckedAccount<'info>, // FixedTicketAccount
+    pub fixed_raffle: AccountLoader<'info, FixedTicketAccount>,
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // spl raffles only
     #[account(
         mut,
         constraint = raffle.key() == escrow_token_cost.owner,
         constraint = escrow_token_cost.mint == mint_cost.key()
     )]
-    pub escrow_token_cost: Account<'info, TokenAccount>
+    pub escrow_token_cost: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        seeds = [CONFIG_SEED], bump = config.bump
+    )]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+    // native raffles only
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, raffle.key().as_ref()], bump = raffle.vault_bump
+    )]
+    pub vault: Option<SystemAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 1,
+        seeds = [ENTRY_SEED, raffle.key().as_ref(), payer.key().as_ref()], bump,
+    )]
+    pub buyer_entry: Box<Account<'info, BuyerEntry>>,
+    // gated raffles only, the buyer's token account holding the gate mint or collection nft
+    #[account(
+        constraint = payer.key == &gate_token.owner
+    )]
+    pub gate_token: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// CHECK: collection gated raffles only, metadata of gate_token's mint
+    pub gate_metadata: Option<UncheckedAccount<'info>>,
+}
 
+#[derive(Accounts)]
+pub struct CloseEntry<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: has to be gone or created again since the entry's purchases, the entry's seeds tie it to the address
+    pub raffle: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [ENTRY_SEED, raffle.key().as_ref(), payer.key().as_ref()], bump = buyer_entry.bump
+    )]
+    pub buyer_entry: Account<'info, BuyerEntry>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], bump = config.bump
+    )]
+    pub config: Box<Account<'info, ProtocolConfig>>,
 }
 
 #[derive(Accounts)]
@@ -165,13 +390,13 @@ pub struct DrawWinner<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     pub recipient: SystemAccount<'info>,
-    pub mint_cost: Account<'info, Mint>,
-    pub mint_prize: Box<Account<'info, Mint>>,
+    pub mint_cost: InterfaceAccount<'info, Mint>,
+    pub mint_prize: Box<InterfaceAccount<'info, Mint>>,
+    // any tier's mint, checked against the winner's tier
     #[account(
         mut,
         constraint = raffle.owner == *payer.key,
-        constraint = raffle.mint == mint_cost.key(),
-        constraint = raffle.prize == mint_prize.key()
+        constraint = raffle.mint == mint_cost.key()
     )]
     pub raffle: Box<Account<'info, RaffleAccount>>,
     #[account(
@@ -179,49 +404,214 @@ pub struct DrawWinner<'info> {
         constraint = recipient.key == &token_prize.owner,
         constraint = mint_prize.key() == token_prize.mint
     )]
-    pub token_prize: Account<'info, TokenAccount>,
+    pub token_prize: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = raffle.key() == escrow_token_prize.owner,
+        constraint = escrow_token_prize.mint == mint_prize.key()
+    )]
+    pub escrow_token_prize: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = fixed_raffle.load()?.raffle_id == raffle.key() @ CustomError::InputError
+    )]
+    pub fixed_raffle: AccountLoader<'info, FixedTicketAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPrize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub winner: SystemAccount<'info>,
+    // any tier's mint, checked against the ticket's tier
+    pub mint_prize: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut)]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    #[account(mut)]
+    /// CHECK: the winner's ATA, created if it doesn't exist yet
+    pub token_prize: UncheckedAccount<'info>,
     #[account(
         mut,
         constraint = raffle.key() == escrow_token_prize.owner,
         constraint = escrow_token_prize.mint == mint_prize.key()
     )]
-    pub escrow_token_prize: Box<Account<'info, TokenAccount>>,
-    pub token_program: Program<'info, Token>,
-    /// CHECK: see constraint
-    pub fixed_raffle: UncheckedAccount<'info>, // FixedTicketAccount
+    pub escrow_token_prize: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = fixed_raffle.load()?.raffle_id == raffle.key() @ CustomError::InputError
+    )]
+    pub fixed_raffle: AccountLoader<'info, FixedTicketAccount>,
+    pub associated_token: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRaffle<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    #[account(
+        seeds = [CONFIG_SEED], bump = config.bump
+    )]
+    pub config: Box<Account<'info, ProtocolConfig>>,
 }
 
+#[derive(Accounts)]
+pub struct ClaimRefund<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub mint_cost: InterfaceAccount<'info, Mint>,
+    // spl raffles only
+    #[account(
+        mut,
+        constraint = payer.key == &token_cost.owner,
+        constraint = mint_cost.key() == token_cost.mint
+    )]
+    pub token_cost: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = raffle.mint == mint_cost.key()
+    )]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    // refunds come off the entry's ticket count, the entry itself stays until close_entry
+    #[account(
+        mut,
+        seeds = [ENTRY_SEED, raffle.key().as_ref(), payer.key().as_ref()], bump = buyer_entry.bump
+    )]
+    pub buyer_entry: Account<'info, BuyerEntry>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // spl raffles only
+    #[account(
+        mut,
+        constraint = raffle.key() == escrow_token_cost.owner,
+        constraint = escrow_token_cost.mint == mint_cost.key()
+    )]
+    pub escrow_token_cost: Option<InterfaceAccount<'info, TokenAccount>>,
+    // native raffles only
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, raffle.key().as_ref()], bump = raffle.vault_bump
+    )]
+    pub vault: Option<SystemAccount<'info>>,
+}
+
+// anyone can crank it, the seed is fixed before the first draw and a missed reveal only leads to refunds
 #[derive(Accounts)]
 pub struct SetWinner<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(mut)]
-    pub mint_cost: Account<'info, Mint>,
-    pub mint_prize: Box<Account<'info, Mint>>,
+    pub mint_cost: InterfaceAccount<'info, Mint>,
+    pub mint_prize: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
-        constraint = raffle.owner == *payer.key || payer.key.to_string() == MOON_KEY,
         constraint = raffle.mint == mint_cost.key(),
         constraint = raffle.prize == mint_prize.key()
     )]
     pub raffle: Box<Account<'info, RaffleAccount>>,
     #[account(
         mut,
-//        constraint = fixed_raffle.raffle_id == raffle.key()
+        constraint = fixed_raffle.load()?.raffle_id == raffle.key() @ CustomError::InputError
+    )]
+    pub fixed_raffle: AccountLoader<'info, FixedTicketAccount>,
+    #[account(
+        seeds = [b"randomness", raffle.key().as_ref()], bump
     )]
-    /// CHECK: see constraint
-    pub fixed_raffle: UncheckedAccount<'info>, // FixedTicketAccount
-    /// CHECK: RecentSlothash
-    pub slot_hashes: UncheckedAccount<'info>
+    /// CHECK: only read for oracle raffles, see OracleProvider
+    pub randomness_request: UncheckedAccount<'info>,
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    /// CHECK: slot hashes sysvar, oracle raffles mix in a slot after the fulfilment
+    pub slot_hashes: UncheckedAccount<'info>,
 }
 
-// PDA of < owner - token_mint - prize_mint >
+#[derive(Accounts)]
+pub struct LockRaffle<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+}
+
+// anyone can crank it, the hash is fixed by the lock and keeping it only stops it from expiring
+#[derive(Accounts)]
+pub struct RecordSlotHash<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    /// CHECK: slot hashes sysvar, read by hand since it's too big to deserialize
+    pub slot_hashes: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RevealSecret<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = raffle.owner == *payer.key
+    )]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    /// CHECK: slot hashes sysvar, read by hand since it's too big to deserialize
+    pub slot_hashes: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RequestRandomness<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        constraint = raffle.source == RandomnessSource::Oracle
+    )]
+    pub raffle: Box<Account<'info, RaffleAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 32 + 32 + 1 + 8 + 1,
+        seeds = [b"randomness", raffle.key().as_ref()], bump,
+    )]
+    pub request: Account<'info, RandomnessRequest>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FulfillRandomness<'info> {
+    #[account(mut)]
+    pub oracle: Signer<'info>,
+    #[account(
+        mut,
+        constraint = request.oracle == *oracle.key
+    )]
+    pub request: Account<'info, RandomnessRequest>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions sysvar
+    pub instructions: UncheckedAccount<'info>,
+}
+
+// PDA of < "config" >, one per deployment
+#[account]
+pub struct ProtocolConfig {
+    pub admin: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub fee_bps: u16,
+    pub proceeds_fee_bps: u16,
+    pub max_proceeds_fee_bps: u16,
+    pub paused: bool,
+    pub bump: u8,
+}
+
+// PDA of < owner - token_mint - prize_mint - nonce >
 #[account]
 pub struct RaffleAccount {
     pub id: Pubkey,
     pub owner: Pubkey,
     pub mint: Pubkey,
     pub prize: Pubkey,
+    pub nonce: u64,
     pub prize_quantity: u64,
     pub tickets_purchased: u64,
     pub price: u64,
@@ -230,52 +620,264 @@ pub struct RaffleAccount {
     pub end: i64,
     pub ticket_count: u64,
     pub max_entries: u64,
+    pub min_tickets: u64,
+    // 0 for no cap, counted on each buyer's BuyerEntry
+    pub max_tickets_per_wallet: u64,
+    pub tickets_refunded: u64,
+    // rent buyers paid to grow the ticket account, moved to the config on close
+    pub buyer_rent: u64,
     pub per_win: u64,
+    pub fee_bps: u16,
     pub cost_decimals: u8,
     pub prize_decimals: u8,
     pub win_multiple: bool,
+    pub refunding: bool,
+    pub native: bool,
     pub bump: u8,
+    pub vault_bump: u8,
     pub burn: bool,
     pub fixed: bool,
-    pub unique_entries: u16,
+    pub unique_entries: u32,
     pub winners_selected: bool,
     pub sent_out: u8,
+    pub commitment: [u8; 32],
+    // hash of the slot mixed into the seed, ENTROPY_DELAY past the lock or the oracle's fulfilment
+    // commit-reveal raffles keep it as soon as record_slot_hash or the reveal sees it
+    pub slot_hash: [u8; 32],
+    pub seed: [u8; 32],
+    pub locked_at: i64,
+    pub lock_slot: u64,
+    pub draw_nonce: u64,
+    pub revealed: bool,
+    pub source: RandomnessSource,
+    pub oracle: Pubkey,
+    // verified metaplex collection of the prize, default when the raffle didn't ask for verification
+    pub collection: Pubkey,
+    // merkle root of allowed buyers, all zeroes for an open raffle
+    pub allowlist: [u8; 32],
+    pub gate: TicketGate,
     pub description: String,
     pub nft_image: String,
     pub nft_uri: String,
-    pub winners: Vec<u64>
+    pub tiers: Vec<PrizeTier>,
+    pub bundle: Vec<Pubkey>,
+    pub winners: Vec<u64>,
+    pub claimed: Vec<bool>
+}
+
+impl RaffleAccount {
+    // the most the account can serialize to, with every winner drawn and the largest gate
+    pub fn space(&self) -> usize {
+        // discriminator, keys, u64s, the small fields, hashes, lock fields, oracle/collection/allowlist, gate, string and vec lengths
+        let fixed = 8 + 32 * 4 + 8 * 14 + 2 + 9 + 4 + 2 + 32 * 3 + 8 * 3 + 2 + 32 * 3 + (1 + 32 + 8) + 4 * 3 + 4 * 4;
+
+        fixed
+            + self.description.len() + self.nft_image.len() + self.nft_uri.len()
+            + self.tiers.len() * (32 + 8 + 8 + 1 + 1)
+            + self.bundle.len() * 32
+            + self.winner_count() * (8 + 1)
+    }
+
+    pub fn winner_count(&self) -> usize {
+        self.tier_winner_count() + self.bundle.len()
+    }
+
+    // can never be drawn, either under min_tickets or too few tickets for every winner to hold a different one
+    pub fn undersold(&self) -> bool {
+        self.tickets_purchased < self.min_tickets || (!self.win_multiple && self.tickets_purchased < self.winner_count() as u64)
+    }
+
+    pub fn tier_winner_count(&self) -> usize {
+        self.tiers.iter().map(|tier| tier.winners() as usize).sum()
+    }
+
+    // winners fill the tiers in order, so each tier owns a run of slots in `winners`
+    pub fn tier_slots(&self, tier: usize) -> Range<usize> {
+        let start = self.tiers[..tier].iter().map(|tier| tier.winners() as usize).sum();
+        start..start + self.tiers[tier].winners() as usize
+    }
+
+    pub fn tier_of(&self, slot: usize) -> Option<usize> {
+        (0..self.tiers.len()).find(|tier| self.tier_slots(*tier).contains(&slot))
+    }
+
+    // bundle nfts come after every tier, one per winner in the order they were added
+    pub fn bundle_slot(&self, index: usize) -> usize {
+        self.tier_winner_count() + index
+    }
+
+    // what the winner in this slot gets paid, a bundle nft pays out like a single-winner tier of one token
+    pub fn slot_prize(&self, slot: usize) -> Option<PrizeTier> {
+        if let Some(tier) = self.tier_of(slot) {
+            return Some(self.tiers[tier].clone());
+        }
+
+        self.bundle.get(slot.checked_sub(self.tier_winner_count())?).map(|mint| PrizeTier {
+            mint: *mint,
+            quantity: 1,
+            per_win: 1,
+            decimals: 0,
+            closed: false
+        })
+    }
+}
+
+// grows the raffle account to its worst case size, the payer covers the extra rent
+pub fn fit_raffle<'info>(raffle: &Account<'info, RaffleAccount>, payer: &Signer<'info>, system_program: &Program<'info, System>) -> Result<()> {
+    let space = raffle.space();
+    let info = raffle.to_account_info();
+
+    if space > MAX_RAFFLE_SPACE {
+        return err!(CustomError::RaffleTooLarge);
+    }
+
+    if space <= info.data_len() {
+        return Ok(());
+    }
+
+    let rent = Rent::get()?.minimum_balance(space).saturating_sub(info.lamports());
+
+    if rent > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(system_program.to_account_info(), anchor_lang::system_program::Transfer {
+                from: payer.to_account_info(),
+                to: info.clone()
+            }),
+            rent,
+        )?;
+    }
+
+    info.realloc(space, false)?;
+
+    Ok(())
+}
+
+// PDA of < "randomness" - raffle >
+#[account]
+pub struct RandomnessRequest {
+    pub raffle: Pubkey,
+    pub oracle: Pubkey,
+    pub alpha: [u8; 32],
+    pub output: [u8; 32],
+    pub fulfilled: bool,
+    // the seed mixes in the hash of the first slot ENTROPY_DELAY past this one
+    pub fulfilled_slot: u64,
+    pub bump: u8,
 }
 
+// PDA of < "entry" - raffle - buyer >, one per wallet that bought into a raffle
 #[account]
+pub struct BuyerEntry {
+    pub raffle: Pubkey,
+    pub buyer: Pubkey,
+    // date_created of the raffle the tickets count for, the raffle's address comes back if it's closed and created again
+    pub raffle_created: i64,
+    pub tickets: u64,
+    // rent this buyer put into the ticket account, handed back by close_entry
+    pub rent_paid: u64,
+    pub bump: u8,
+}
+
+// created by the client with room for its runs, which follow this header, see tickets.rs
+// packed so the runs line up at any offset and the layout matches the old borsh one
+#[account(zero_copy(unsafe))]
+#[repr(C, packed)]
 pub struct FixedTicketAccount {
     pub raffle_id: Pubkey,
-    pub entries: Vec<FixedEntry>,
+    pub count: u32,
 }
 
 //
 //
 //
 
-#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
+// a run of tickets, `end` is the raffle's running ticket total after this run
+#[zero_copy(unsafe)]
+#[repr(C, packed)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable)]
 pub struct FixedEntry {
     pub buyer: Pubkey,
-    pub wins: u8
+    pub end: u64
+}
+
+// tier 0 is the raffle's own prize, the rest are added with add_prize_tier
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone)]
+pub struct PrizeTier {
+    pub mint: Pubkey,
+    pub quantity: u64,
+    pub per_win: u64,
+    pub decimals: u8,
+    pub closed: bool
+}
+
+impl PrizeTier {
+    pub fn winners(&self) -> u64 {
+        self.quantity / self.per_win
+    }
+}
+
+// what a buyer has to hold to get tickets
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TicketGate {
+    Open,
+    Token { mint: Pubkey, amount: u64 },
+    Collection { collection: Pubkey }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RandomnessSource {
+    SlotHash,
+    Oracle
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug)]
+pub struct ConfigData {
+    pub admin: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub fee_bps: u16,
+    pub proceeds_fee_bps: u16,
+    pub max_proceeds_fee_bps: u16,
+    pub paused: bool,
+}
+
+impl ConfigData {
+    pub fn is_valid(&self) -> bool {
+        self.fee_bps <= MAX_BPS && self.max_proceeds_fee_bps <= MAX_BPS && self.proceeds_fee_bps <= self.max_proceeds_fee_bps
+    }
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Debug)]
 pub struct CreateRaffleData {
+    // picked by the creator, lets one creator run several raffles on the same mints
+    pub nonce: u64,
     pub prize_quantity: u64,
     pub price:    u64,
     pub start:    i64,
     pub end:      i64,
     pub max_entries: u64,
+    pub min_tickets: u64,
+    pub max_tickets_per_wallet: u64,
     pub per_win:     u64,
     pub cost_decimals: u8,
     pub prize_decimals: u8,
     pub win_multiple: bool,
     pub burn: bool,
     pub fixed: bool,
+    pub native: bool,
+    pub commitment: [u8; 32],
+    pub source: RandomnessSource,
+    pub oracle: Pubkey,
+    pub verify_metadata: bool,
+    pub allowlist: Option<[u8; 32]>,
+    pub gate: TicketGate,
     pub description: String,
     pub nft_uri: String,
     pub nft_image: String
 }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug)]
+pub struct PrizeTierData {
+    pub quantity: u64,
+    pub per_win: u64,
+    pub decimals: u8
+}
@@ -0,0 +1,55 @@
+use {
+    anchor_lang::prelude::*,
+    crate::state::*,
+};
+
+// tickets are stored as runs, each entry is a buyer and the running ticket total at the end of their run
+// so a purchase adds at most one entry and a ticket is found by binary search over the totals
+
+// the runs sit right after the FixedTicketAccount header, only the first `count` of them are in use
+pub fn ticket_runs(data: &[u8]) -> &[FixedEntry] {
+    let header: &FixedTicketAccount = bytemuck::from_bytes(&data[8 .. RAFFLE_ENTRY_OFFSET]);
+    let count = header.count as usize;
+
+    bytemuck::cast_slice(&data[RAFFLE_ENTRY_OFFSET .. RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * count])
+}
+
+pub fn ticket_runs_mut(data: &mut [u8]) -> &mut [FixedEntry] {
+    let header: &FixedTicketAccount = bytemuck::from_bytes(&data[8 .. RAFFLE_ENTRY_OFFSET]);
+    let count = header.count as usize;
+
+    bytemuck::cast_slice_mut(&mut data[RAFFLE_ENTRY_OFFSET .. RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * count])
+}
+
+pub fn range_start(runs: &[FixedEntry], index: usize) -> u64 {
+    if index == 0 { 0 } else { runs[index - 1].end }
+}
+
+// the run holding this ticket, the first whose running total is past it
+pub fn find_range(runs: &[FixedEntry], ticket: u64) -> Option<usize> {
+    let index = runs.partition_point(|run| run.end <= ticket);
+    (index < runs.len()).then_some(index)
+}
+
+pub fn ticket_buyer(runs: &[FixedEntry], ticket: u64) -> Option<Pubkey> {
+    find_range(runs, ticket).map(|index| runs[index].buyer)
+}
+
+// back to back purchases from the same buyer extend their run instead of starting a new one
+pub fn needs_range(runs: &[FixedEntry], buyer: &Pubkey) -> bool {
+    runs.last().is_none_or(|run| run.buyer != *buyer)
+}
+
+// the account has to already be big enough for a new run, see needs_range
+pub fn push_tickets(data: &mut [u8], buyer: &Pubkey, total: u64) {
+    if needs_range(ticket_runs(data), buyer) {
+        let header: &mut FixedTicketAccount = bytemuck::from_bytes_mut(&mut data[8 .. RAFFLE_ENTRY_OFFSET]);
+        header.count += 1;
+
+        let runs = ticket_runs_mut(data);
+        runs[runs.len() - 1].buyer = *buyer;
+    }
+
+    let runs = ticket_runs_mut(data);
+    runs[runs.len() - 1].end = total;
+}
@@ -0,0 +1,59 @@
+use {
+    anchor_lang::prelude::*,
+    anchor_lang::solana_program::program::invoke,
+    anchor_spl::token_2022::spl_token_2022::{
+        self,
+        extension::{
+            BaseStateWithExtensions,
+            StateWithExtensions,
+            transfer_fee::{instruction::harvest_withheld_tokens_to_mint, TransferFeeConfig},
+        },
+        state::Mint,
+    },
+    crate::error::*,
+};
+
+// token-2022 mints can take a cut of every transfer, legacy mints and mints without the extension never do
+fn fee_config(mint: &AccountInfo) -> Result<Option<TransferFeeConfig>> {
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(None);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<Mint>::unpack(&data)?;
+
+    Ok(state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+// what has to be sent so that exactly `amount` lands on the other side, escrows always hold what the raffle says they hold
+pub fn gross_amount(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let Some(config) = fee_config(mint)? else {
+        return Ok(amount);
+    };
+
+    let epoch = Clock::get()?.epoch;
+
+    let gross = config.calculate_inverse_epoch_fee(epoch, amount)
+        .and_then(|fee| amount.checked_add(fee))
+        .ok_or(CustomError::InputError)?;
+
+    // a 100% fee has no inverse, don't let a deposit come up short
+    if config.calculate_epoch_fee(epoch, gross).and_then(|fee| gross.checked_sub(fee)) != Some(amount) {
+        return err!(CustomError::InputError);
+    }
+
+    Ok(gross)
+}
+
+// fees withheld on an escrow block closing it, they're moved to the mint where the fee authority collects them
+pub fn harvest_withheld<'info>(token_program: &AccountInfo<'info>, mint: &AccountInfo<'info>, account: &AccountInfo<'info>) -> Result<()> {
+    if fee_config(mint)?.is_none() {
+        return Ok(());
+    }
+
+    let ix = harvest_withheld_tokens_to_mint(token_program.key, mint.key, &[account.key])?;
+
+    invoke(&ix, &[mint.clone(), account.clone()])?;
+
+    Ok(())
+}
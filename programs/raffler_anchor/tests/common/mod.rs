@@ -0,0 +1,1050 @@
+#![allow(dead_code)]
+
+use {
+    anchor_lang::{prelude::*, AccountDeserialize, Event, InstructionData, ToAccountMetas},
+    base64::Engine,
+    anchor_spl::{
+        associated_token,
+        token::spl_token::{self, native_mint},
+        token_2022::spl_token_2022::{
+            self,
+            extension::{transfer_fee, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+        },
+    },
+    raffler_anchor::{metadata::*, randomness::{commitment, ENTROPY_DELAY}, state::*},
+    solana_program_test::*,
+    solana_sdk::{
+        account::Account,
+        ed25519_program,
+        entrypoint::ProgramResult,
+        instruction::{Instruction, InstructionError},
+        program_pack::Pack,
+        program_stubs::{self, SyscallStubs},
+        signature::Keypair,
+        signer::Signer,
+        system_instruction,
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+// the addresses come from the client, so the tests exercise the same derivations services use
+pub use raffler_client::pda::{config_address, entry_address, raffle_address, randomness_address, token_address, vault_address};
+
+fn entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // anchor wants the account infos to live as long as their data, the test runtime hands them out for this call only
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    raffler_anchor::entry(program_id, accounts, data)
+}
+
+pub fn ata(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    token_address(owner, mint, &spl_token::ID)
+}
+
+// the program's or anchor's error code, anything else is a test bug
+pub fn assert_error<T: std::fmt::Debug>(result: std::result::Result<T, BanksClientError>, code: impl Into<u32>) {
+    match result {
+        Err(BanksClientError::TransactionError(TransactionError::InstructionError(_, InstructionError::Custom(found)))) => {
+            assert_eq!(found, code.into());
+        }
+        other => panic!("expected custom error, got {:?}", other),
+    }
+}
+
+// program-test runs the program natively and its stubs drop sol_log_data, so emit! never reaches the logs
+// this forwards it through sol_log instead and hands everything else to program-test
+struct EventStubs(Box<dyn SyscallStubs>);
+
+struct NoStubs;
+impl SyscallStubs for NoStubs {}
+
+impl SyscallStubs for EventStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        self.0.sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(|field| base64::engine::general_purpose::STANDARD.encode(field)).collect();
+        self.0.sol_log(&format!("Program data: {}", fields.join(" ")));
+    }
+
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+// has to run after program-test installed its own stubs, every test binary shares them
+fn capture_events() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+
+    ONCE.call_once(|| {
+        let stubs = program_stubs::set_syscall_stubs(Box::new(NoStubs));
+        program_stubs::set_syscall_stubs(Box::new(EventStubs(stubs)));
+    });
+}
+
+// every event of this type the transaction emitted, in order
+pub fn events<T: Event>(logs: &[String]) -> Vec<T> {
+    logs.iter()
+        // a deployed program logs them bare, the native one through sol_log, see EventStubs
+        .filter_map(|log| log.strip_prefix("Program data: ").or_else(|| log.strip_prefix("Program log: Program data: ")))
+        .filter_map(|data| base64::engine::general_purpose::STANDARD.decode(data).ok())
+        .filter(|data| data.len() >= 8 && data[..8] == T::DISCRIMINATOR)
+        .map(|data| T::deserialize(&mut &data[8..]).unwrap())
+        .collect()
+}
+
+pub struct TestEnv {
+    pub context: ProgramTestContext,
+    pub admin: Keypair,
+    pub fee_recipient: Keypair,
+}
+
+impl TestEnv {
+    // the config is written straight into genesis, initialize_config needs an upgradeable deploy to check the authority
+    pub async fn new() -> Self {
+        let mut program_test = ProgramTest::new("raffler_anchor", raffler_anchor::ID, processor!(entry));
+
+        let admin = Keypair::new();
+        let fee_recipient = Keypair::new();
+
+        let config = ProtocolConfig {
+            admin: admin.pubkey(),
+            fee_recipient: fee_recipient.pubkey(),
+            fee_bps: 1000,
+            proceeds_fee_bps: 0,
+            max_proceeds_fee_bps: 500,
+            paused: false,
+            bump: Pubkey::find_program_address(&[CONFIG_SEED], &raffler_anchor::ID).1,
+        };
+
+        let mut data = vec![];
+        config.try_serialize(&mut data).unwrap();
+
+        program_test.add_account(config_address(), Account {
+            lamports: 10_000_000,
+            data,
+            owner: raffler_anchor::ID,
+            ..Account::default()
+        });
+
+        let mut native = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            decimals: 9,
+            is_initialized: true,
+            ..spl_token::state::Mint::default()
+        }.pack_into_slice(&mut native);
+
+        program_test.add_account(native_mint::ID, Account {
+            lamports: 10_000_000,
+            data: native,
+            owner: spl_token::ID,
+            ..Account::default()
+        });
+
+        for key in [admin.pubkey(), fee_recipient.pubkey()] {
+            program_test.add_account(key, Account {
+                lamports: 10_000_000_000,
+                ..Account::default()
+            });
+        }
+
+        let context = program_test.start_with_context().await;
+        capture_events();
+
+        TestEnv {
+            context,
+            admin,
+            fee_recipient,
+        }
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        self.context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+    }
+
+    pub async fn warp_to(&mut self, unix_timestamp: i64) {
+        let mut clock = self.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    // past the lock's ENTROPY_DELAY so reveal_secret finds its slot hash, the clock keeps its time
+    pub async fn pass_entropy_delay(&mut self) {
+        let clock = self.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+
+        // a warped bank only records its parent in SlotHashes, so the slot past the delay needs a bank after it
+        self.context.warp_to_slot(clock.slot + ENTROPY_DELAY + 1).unwrap();
+        self.context.warp_to_slot(clock.slot + ENTROPY_DELAY + 2).unwrap();
+        self.warp_to(clock.unix_timestamp).await;
+    }
+
+    // the test payer pays the fees, everyone else just signs
+    pub async fn process(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> std::result::Result<(), BanksClientError> {
+        let blockhash = self.context.banks_client.get_new_latest_blockhash(&self.context.last_blockhash).await.unwrap();
+        self.context.last_blockhash = blockhash;
+
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+
+        let tx = Transaction::new_signed_with_payer(instructions, Some(&self.context.payer.pubkey()), &all_signers, blockhash);
+
+        self.context.banks_client.process_transaction(tx).await
+    }
+
+    // like process, but hands back the program's logs too
+    pub async fn process_with_logs(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> (std::result::Result<(), BanksClientError>, Vec<String>) {
+        let blockhash = self.context.banks_client.get_new_latest_blockhash(&self.context.last_blockhash).await.unwrap();
+        self.context.last_blockhash = blockhash;
+
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+
+        let tx = Transaction::new_signed_with_payer(instructions, Some(&self.context.payer.pubkey()), &all_signers, blockhash);
+
+        // this runs straight on the bank, the last process call can still be holding its locks
+        let result = loop {
+            let result = self.context.banks_client.process_transaction_with_metadata(tx.clone()).await.unwrap();
+
+            if result.result != Err(TransactionError::AccountInUse) {
+                break result;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        let logs = result.metadata.map(|metadata| metadata.log_messages).unwrap_or_default();
+
+        (result.result.map_err(BanksClientError::TransactionError), logs)
+    }
+
+    // moves past the raffle's end with a few fresh slot hashes to lock on
+    pub async fn end_raffle(&mut self, end: i64) {
+        let slot = self.context.banks_client.get_root_slot().await.unwrap();
+        self.context.warp_to_slot(slot + 4).unwrap();
+        self.warp_to(end + 1).await;
+    }
+
+    // the admin signs, `update` gets the config as it is now
+    pub async fn update_config(&mut self, update: impl FnOnce(&mut ConfigData)) -> std::result::Result<(), BanksClientError> {
+        let config: ProtocolConfig = self.account(&config_address()).await;
+        let mut data = ConfigData {
+            admin: config.admin,
+            fee_recipient: config.fee_recipient,
+            fee_bps: config.fee_bps,
+            proceeds_fee_bps: config.proceeds_fee_bps,
+            max_proceeds_fee_bps: config.max_proceeds_fee_bps,
+            paused: config.paused,
+        };
+
+        update(&mut data);
+
+        let accounts = raffler_anchor::accounts::UpdateConfig {
+            payer: self.admin.pubkey(),
+            config: config_address(),
+        };
+
+        let instruction = Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::UpdateConfig { data }.data(),
+        };
+
+        let admin = self.admin.insecure_clone();
+        self.process(&[instruction], &[&admin]).await
+    }
+
+    pub async fn account<T: AccountDeserialize>(&mut self, key: &Pubkey) -> T {
+        let account = self.context.banks_client.get_account(*key).await.unwrap().unwrap();
+        T::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    pub async fn data(&mut self, key: &Pubkey) -> Vec<u8> {
+        self.context.banks_client.get_account(*key).await.unwrap().unwrap().data
+    }
+
+    pub async fn lamports(&mut self, key: &Pubkey) -> u64 {
+        self.context.banks_client.get_balance(*key).await.unwrap()
+    }
+
+    pub async fn funded_keypair(&mut self, lamports: u64) -> Keypair {
+        let keypair = Keypair::new();
+        let transfer = system_instruction::transfer(&self.context.payer.pubkey(), &keypair.pubkey(), lamports);
+
+        self.process(&[transfer], &[]).await.unwrap();
+
+        keypair
+    }
+
+    // mints are owned by the test payer
+    pub async fn create_mint(&mut self, decimals: u8) -> Pubkey {
+        self.create_mint_with(decimals, None).await
+    }
+
+    // a token-2022 mint taking `fee_bps` of every transfer when there's a fee, a legacy one otherwise
+    pub async fn create_mint_with(&mut self, decimals: u8, fee_bps: Option<u16>) -> Pubkey {
+        let mint = Keypair::new();
+        let payer = self.context.payer.pubkey();
+
+        let (token_program, space) = match fee_bps {
+            Some(_) => (spl_token_2022::ID, ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[ExtensionType::TransferFeeConfig]).unwrap()),
+            None => (spl_token::ID, spl_token::state::Mint::LEN),
+        };
+
+        let rent = self.context.banks_client.get_rent().await.unwrap().minimum_balance(space);
+        let mut instructions = vec![system_instruction::create_account(&payer, &mint.pubkey(), rent, space as u64, &token_program)];
+
+        // extensions have to be set up before the mint is initialized
+        if let Some(fee_bps) = fee_bps {
+            instructions.push(transfer_fee::instruction::initialize_transfer_fee_config(&token_program, &mint.pubkey(), Some(&payer), Some(&payer), fee_bps, u64::MAX).unwrap());
+        }
+
+        instructions.push(spl_token_2022::instruction::initialize_mint2(&token_program, &mint.pubkey(), &payer, None, decimals).unwrap());
+
+        self.process(&instructions, &[&mint]).await.unwrap();
+
+        mint.pubkey()
+    }
+
+    // whichever token program owns the mint
+    pub async fn token_program(&mut self, mint: &Pubkey) -> Pubkey {
+        self.context.banks_client.get_account(*mint).await.unwrap().unwrap().owner
+    }
+
+    pub async fn create_ata(&mut self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        let token_program = self.token_program(mint).await;
+        let address = token_address(owner, mint, &token_program);
+
+        // the associated token program's create, spelled out since only its id is re-exported
+        let instruction = Instruction {
+            program_id: associated_token::ID,
+            accounts: vec![
+                AccountMeta::new(self.context.payer.pubkey(), true),
+                AccountMeta::new(address, false),
+                AccountMeta::new_readonly(*owner, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(anchor_lang::system_program::ID, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+            data: vec![0],
+        };
+
+        self.process(&[instruction], &[]).await.unwrap();
+
+        address
+    }
+
+    pub async fn mint_to(&mut self, mint: &Pubkey, account: &Pubkey, amount: u64) {
+        let token_program = self.token_program(mint).await;
+        let instruction = spl_token_2022::instruction::mint_to(&token_program, mint, account, &self.context.payer.pubkey(), &[], amount).unwrap();
+
+        self.process(&[instruction], &[]).await.unwrap();
+    }
+
+    pub async fn token_balance(&mut self, account: &Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(*account).await.unwrap().unwrap();
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data).unwrap().base.amount
+    }
+
+    // transfer fees held back on the account, zero for legacy accounts
+    pub async fn withheld(&mut self, account: &Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(*account).await.unwrap().unwrap();
+        let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data).unwrap();
+
+        state.get_extension::<transfer_fee::TransferFeeAmount>().map(|fees| fees.withheld_amount.into()).unwrap_or(0)
+    }
+
+    // transfer fees harvested into the mint
+    pub async fn mint_withheld(&mut self, mint: &Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(*mint).await.unwrap().unwrap();
+        let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data).unwrap();
+
+        state.get_extension::<transfer_fee::TransferFeeConfig>().map(|config| config.withheld_amount.into()).unwrap_or(0)
+    }
+
+    pub async fn supply(&mut self, mint: &Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(*mint).await.unwrap().unwrap();
+        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data).unwrap().base.supply
+    }
+
+    // an ATA holding `amount` of the mint
+    pub async fn fund_tokens(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) -> Pubkey {
+        let address = self.create_ata(owner, mint).await;
+        self.mint_to(mint, &address, amount).await;
+
+        address
+    }
+
+    // metaplex metadata for the mint, written straight into the bank since the metadata program isn't loaded
+    pub fn set_metadata(&mut self, metadata: &MintMetadata) {
+        let mut data = vec![];
+        metadata.serialize(&mut data).unwrap();
+
+        self.context.set_account(&metadata_address(&metadata.mint), &Account {
+            lamports: 10_000_000,
+            data,
+            owner: METADATA_PROGRAM_ID,
+            ..Account::default()
+        }.into());
+    }
+}
+
+// a verified metadata account for the mint with the given uri and collection
+pub fn mint_metadata(mint: &Pubkey, uri: &str, collection: Option<MetadataCollection>) -> MintMetadata {
+    MintMetadata {
+        key: MintMetadata::KEY,
+        update_authority: Pubkey::new_unique(),
+        mint: *mint,
+        name: "prize".to_string(),
+        symbol: String::new(),
+        // stored padded, like metaplex does
+        uri: format!("{:\0<200}", uri),
+        seller_fee_basis_points: 0,
+        creators: None,
+        primary_sale_happened: false,
+        is_mutable: true,
+        edition_nonce: None,
+        token_standard: None,
+        collection,
+    }
+}
+
+// an ed25519 program instruction carrying the signer's signature of `message`, laid out the way verify_oracle_proof reads it
+pub fn ed25519_instruction(signer: &Keypair, message: &[u8]) -> (Instruction, [u8; 64]) {
+    let signature: [u8; 64] = signer.sign_message(message).into();
+
+    let public_key_offset: u16 = 16;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+
+    let mut data = vec![1, 0];
+
+    for field in [signature_offset, u16::MAX, public_key_offset, u16::MAX, message_offset, message.len() as u16, u16::MAX] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+
+    data.extend_from_slice(signer.pubkey().as_ref());
+    data.extend_from_slice(&signature);
+    data.extend_from_slice(message);
+
+    (Instruction { program_id: ed25519_program::ID, accounts: vec![], data }, signature)
+}
+
+// [mint, creator token account, raffle escrow] for each nft, the order add_bundle and close_raffle both take
+pub fn bundle_accounts(creator: &Pubkey, raffle: &Pubkey, bundle: &[Pubkey]) -> Vec<AccountMeta> {
+    bundle.iter().flat_map(|mint| [
+        AccountMeta::new(*mint, false),
+        AccountMeta::new(ata(creator, mint), false),
+        AccountMeta::new(ata(raffle, mint), false),
+    ]).collect()
+}
+
+// what drawn raffles commit to, and the ticket price of every raffle_data raffle
+pub const SECRET: [u8; 32] = [7; 32];
+pub const PRICE: u64 = 1_000_000;
+
+// a dynamic native-priced raffle with a single token prize, the starting point most tests tweak
+pub fn raffle_data(now: i64) -> CreateRaffleData {
+    CreateRaffleData {
+        nonce: 0,
+        prize_quantity: 1,
+        price: PRICE,
+        start: now - 60,
+        end: now + 60 * 60,
+        max_entries: 0,
+        min_tickets: 0,
+        max_tickets_per_wallet: 0,
+        per_win: 1,
+        cost_decimals: 9,
+        prize_decimals: 0,
+        win_multiple: false,
+        burn: false,
+        fixed: false,
+        native: true,
+        commitment: [0; 32],
+        source: RandomnessSource::SlotHash,
+        oracle: Pubkey::default(),
+        verify_metadata: false,
+        allowlist: None,
+        gate: TicketGate::Open,
+        description: String::new(),
+        nft_uri: String::new(),
+        nft_image: String::new(),
+    }
+}
+
+// two winners committed to SECRET, after buy_in the first buyer holds tickets 0-2 and the second 3-4
+pub fn drawn_data(now: i64) -> CreateRaffleData {
+    CreateRaffleData {
+        prize_quantity: 2,
+        commitment: commitment(&SECRET),
+        ..raffle_data(now)
+    }
+}
+
+// five tickets from two buyers, spl buyers are funded with just what they pay
+pub async fn buy_in(env: &mut TestEnv, raffle: &TestRaffle) -> [Keypair; 2] {
+    let buyers = [env.funded_keypair(1_000_000_000).await, env.funded_keypair(1_000_000_000).await];
+
+    for (buyer, amount) in buyers.iter().zip([3, 2]) {
+        if !raffle.native {
+            env.fund_tokens(&buyer.pubkey(), &raffle.mint_cost, amount * PRICE).await;
+        }
+
+        env.process(&[raffle.buy(&buyer.pubkey(), amount)], &[buyer]).await.unwrap();
+    }
+
+    buyers
+}
+
+// who holds `ticket` after buy_in
+pub fn buyer_of(buyers: &[Keypair; 2], ticket: u64) -> &Keypair {
+    if ticket < 3 { &buyers[0] } else { &buyers[1] }
+}
+
+// a raffle created from `data` that buy_in has sold five tickets of
+pub async fn sold(env: &mut TestEnv, data: CreateRaffleData) -> (TestRaffle, [Keypair; 2]) {
+    let raffle = TestRaffle::create(env, data).await;
+    let buyers = buy_in(env, &raffle).await;
+
+    (raffle, buyers)
+}
+
+pub struct TestRaffle {
+    pub creator: Keypair,
+    pub raffle: Pubkey,
+    pub tickets: Pubkey,
+    pub mint_cost: Pubkey,
+    pub mint_prize: Pubkey,
+    pub token_prize: Pubkey,
+    pub escrow_prize: Pubkey,
+    // owns both mints
+    pub token_program: Pubkey,
+    // spl raffles are paid into the raffle's ATA for their own cost mint, native ones into the vault
+    pub native: bool,
+    // from the create transaction
+    pub logs: Vec<String>,
+}
+
+impl TestRaffle {
+    pub async fn create(env: &mut TestEnv, data: CreateRaffleData) -> Self {
+        Self::try_create(env, data, RAFFLE_ENTRY_OFFSET).await.unwrap()
+    }
+
+    // the ticket account is created with `space` bytes in the same transaction
+    pub async fn try_create(env: &mut TestEnv, data: CreateRaffleData, space: usize) -> std::result::Result<Self, BanksClientError> {
+        Self::try_create_with(env, data, space, None).await
+    }
+
+    // spl raffles get a fresh cost mint at `cost_decimals` and every cost ATA close needs, `metadata` is written for the prize mint
+    pub async fn try_create_with(env: &mut TestEnv, data: CreateRaffleData, space: usize, metadata: Option<MintMetadata>) -> std::result::Result<Self, BanksClientError> {
+        Self::try_create_on(env, data, space, metadata, None).await
+    }
+
+    // both mints are token-2022 mints taking `fee_bps` of every transfer, the creator is minted enough to cover the fee on the prize
+    pub async fn create_with_transfer_fee(env: &mut TestEnv, data: CreateRaffleData, fee_bps: u16) -> Self {
+        Self::try_create_on(env, data, RAFFLE_ENTRY_OFFSET, None, Some(fee_bps)).await.unwrap()
+    }
+
+    async fn try_create_on(env: &mut TestEnv, data: CreateRaffleData, space: usize, metadata: Option<MintMetadata>, fee_bps: Option<u16>) -> std::result::Result<Self, BanksClientError> {
+        let creator = env.funded_keypair(10_000_000_000).await;
+        let mint_cost = if data.native { native_mint::ID } else { env.create_mint_with(data.cost_decimals, fee_bps).await };
+        let mint_prize = env.create_mint_with(0, fee_bps).await;
+
+        let token_prize = env.create_ata(&creator.pubkey(), &mint_prize).await;
+        env.mint_to(&mint_prize, &token_prize, data.prize_quantity * if fee_bps.is_some() { 2 } else { 1 }).await;
+
+        let raffle = raffle_address(&creator.pubkey(), &mint_cost, &mint_prize, data.nonce);
+        env.create_ata(&raffle, &mint_prize).await;
+
+        if !data.native {
+            for owner in [raffle, creator.pubkey(), env.fee_recipient.pubkey()] {
+                env.create_ata(&owner, &mint_cost).await;
+            }
+        }
+
+        let prize_metadata = metadata.map(|metadata| {
+            env.set_metadata(&MintMetadata { mint: mint_prize, ..metadata });
+            metadata_address(&mint_prize)
+        });
+
+        Self::launch(env, creator, mint_cost, mint_prize, data, space, prize_metadata).await
+    }
+
+    // creates the raffle again at the same address once this one is closed, the creator is minted its prize again
+    pub async fn recreate(&self, env: &mut TestEnv, data: CreateRaffleData) -> Self {
+        env.mint_to(&self.mint_prize, &self.token_prize, data.prize_quantity).await;
+
+        env.create_ata(&self.raffle, &self.mint_prize).await;
+
+        if !data.native {
+            env.create_ata(&self.raffle, &self.mint_cost).await;
+        }
+
+        Self::launch(env, self.creator.insecure_clone(), self.mint_cost, self.mint_prize, data, RAFFLE_ENTRY_OFFSET, None).await.unwrap()
+    }
+
+    async fn launch(env: &mut TestEnv, creator: Keypair, mint_cost: Pubkey, mint_prize: Pubkey, data: CreateRaffleData, space: usize, prize_metadata: Option<Pubkey>) -> std::result::Result<Self, BanksClientError> {
+        let token_program = env.token_program(&mint_prize).await;
+        let token_prize = token_address(&creator.pubkey(), &mint_prize, &token_program);
+        let raffle = raffle_address(&creator.pubkey(), &mint_cost, &mint_prize, data.nonce);
+        let escrow_prize = token_address(&raffle, &mint_prize, &token_program);
+
+        let tickets = Keypair::new();
+        let rent = env.context.banks_client.get_rent().await.unwrap().minimum_balance(space);
+        let native = data.native;
+
+        let accounts = raffler_anchor::accounts::CreateRaffle {
+            payer: creator.pubkey(),
+            mint_cost,
+            token_prize,
+            mint_prize,
+            raffle,
+            fixed_raffle: tickets.pubkey(),
+            config: config_address(),
+            system_program: anchor_lang::system_program::ID,
+            token_program,
+            escrow_token_prize: escrow_prize,
+            vault: data.native.then(|| vault_address(&raffle)),
+            prize_metadata,
+        };
+
+        let instructions = [
+            system_instruction::create_account(&creator.pubkey(), &tickets.pubkey(), rent, space as u64, &raffler_anchor::ID),
+            Instruction {
+                program_id: raffler_anchor::ID,
+                accounts: accounts.to_account_metas(None),
+                data: raffler_anchor::instruction::CreateRaffle { data }.data(),
+            },
+        ];
+
+        let (result, logs) = env.process_with_logs(&instructions, &[&creator, &tickets]).await;
+        result?;
+
+        Ok(TestRaffle {
+            creator,
+            raffle,
+            tickets: tickets.pubkey(),
+            mint_cost,
+            mint_prize,
+            token_prize,
+            escrow_prize,
+            token_program,
+            native,
+            logs,
+        })
+    }
+
+    // the owner's ATA for the cost mint, None for native raffles
+    pub fn token_cost(&self, owner: &Pubkey) -> Option<Pubkey> {
+        (!self.native).then(|| token_address(owner, &self.mint_cost, &self.token_program))
+    }
+
+    pub fn vault(&self) -> Option<Pubkey> {
+        self.native.then(|| vault_address(&self.raffle))
+    }
+
+    pub fn buy(&self, buyer: &Pubkey, amount: u64) -> Instruction {
+        self.buy_with(buyer, amount, 0, vec![], None, None)
+    }
+
+    // allowlisted and gated purchases, collection gates also take the metadata of the nft in `gate_token`
+    pub fn buy_with(&self, buyer: &Pubkey, amount: u64, allocation: u64, proof: Vec<[u8; 32]>, gate_token: Option<Pubkey>, gate_metadata: Option<Pubkey>) -> Instruction {
+        let accounts = raffler_anchor::accounts::BuyTicket {
+            payer: *buyer,
+            mint_cost: self.mint_cost,
+            token_cost: self.token_cost(buyer),
+            mint_prize: self.mint_prize,
+            raffle: self.raffle,
+            fixed_raffle: self.tickets,
+            system_program: anchor_lang::system_program::ID,
+            token_program: self.token_program,
+            escrow_token_cost: self.token_cost(&self.raffle),
+            config: config_address(),
+            vault: self.vault(),
+            buyer_entry: entry_address(&self.raffle, buyer),
+            gate_token,
+            gate_metadata,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::BuyTicket { amount, allocation, proof }.data(),
+        }
+    }
+
+    pub fn cancel(&self, payer: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::CancelRaffle {
+            payer: *payer,
+            raffle: self.raffle,
+            config: config_address(),
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::CancelRaffle {}.data(),
+        }
+    }
+
+    pub fn claim_refund(&self, buyer: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::ClaimRefund {
+            payer: *buyer,
+            mint_cost: self.mint_cost,
+            token_cost: self.token_cost(buyer),
+            raffle: self.raffle,
+            buyer_entry: entry_address(&self.raffle, buyer),
+            system_program: anchor_lang::system_program::ID,
+            token_program: self.token_program,
+            escrow_token_cost: self.token_cost(&self.raffle),
+            vault: self.vault(),
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::ClaimRefund {}.data(),
+        }
+    }
+
+    // closed by the creator unless `force_close`, in which case the admin signs, proceeds go to the creator either way
+    pub fn close(&self, env: &TestEnv, force_close: bool) -> Instruction {
+        self.close_with_bundle(env, force_close, &[])
+    }
+
+    // `bundle` is the raffle's bundle in order
+    pub fn close_with_bundle(&self, env: &TestEnv, force_close: bool, bundle: &[Pubkey]) -> Instruction {
+        let accounts = raffler_anchor::accounts::CloseRaffle {
+            payer: if force_close { env.admin.pubkey() } else { self.creator.pubkey() },
+            mint_cost: self.mint_cost,
+            token_prize: self.token_prize,
+            token_cost: self.token_cost(&self.creator.pubkey()),
+            mint_prize: self.mint_prize,
+            raffle: self.raffle,
+            owner: self.creator.pubkey(),
+            fixed_raffle: self.tickets,
+            system_program: anchor_lang::system_program::ID,
+            token_program: self.token_program,
+            cost_token_program: self.token_program,
+            escrow_token_prize: self.escrow_prize,
+            escrow_token_cost: self.token_cost(&self.raffle),
+            fee_recipient: env.fee_recipient.pubkey(),
+            fee_token_cost: self.token_cost(&env.fee_recipient.pubkey()),
+            config: config_address(),
+            vault: self.vault(),
+        };
+
+        let mut instruction = Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::CloseRaffle { force_close }.data(),
+        };
+
+        instruction.accounts.extend(bundle_accounts(&self.creator.pubkey(), &self.raffle, bundle));
+
+        instruction
+    }
+
+    // the creator deposits each nft from their ATA, the program creates the escrows
+    pub fn add_bundle(&self, bundle: &[Pubkey]) -> Instruction {
+        let accounts = raffler_anchor::accounts::AddBundle {
+            payer: self.creator.pubkey(),
+            raffle: self.raffle,
+            mint_prize: self.mint_prize,
+            associated_token: associated_token::ID,
+            system_program: anchor_lang::system_program::ID,
+            token_program: self.token_program,
+        };
+
+        let mut instruction = Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::AddBundle {}.data(),
+        };
+
+        instruction.accounts.extend(bundle_accounts(&self.creator.pubkey(), &self.raffle, bundle));
+
+        instruction
+    }
+
+    pub fn lock(&self, payer: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::LockRaffle {
+            payer: *payer,
+            raffle: self.raffle,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::LockRaffle {}.data(),
+        }
+    }
+
+    pub fn record_slot_hash(&self, payer: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::RecordSlotHash {
+            payer: *payer,
+            raffle: self.raffle,
+            slot_hashes: anchor_lang::solana_program::sysvar::slot_hashes::ID,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::RecordSlotHash {}.data(),
+        }
+    }
+
+    pub fn reveal(&self, secret: [u8; 32]) -> Instruction {
+        let accounts = raffler_anchor::accounts::RevealSecret {
+            payer: self.creator.pubkey(),
+            raffle: self.raffle,
+            slot_hashes: anchor_lang::solana_program::sysvar::slot_hashes::ID,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::RevealSecret { secret }.data(),
+        }
+    }
+
+    pub fn set_winner(&self) -> Instruction {
+        self.set_winner_by(&self.creator.pubkey())
+    }
+
+    pub fn set_winner_by(&self, payer: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::SetWinner {
+            payer: *payer,
+            mint_cost: self.mint_cost,
+            mint_prize: self.mint_prize,
+            raffle: self.raffle,
+            fixed_raffle: self.tickets,
+            randomness_request: self.randomness_request(),
+            slot_hashes: anchor_lang::solana_program::sysvar::slot_hashes::ID,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::SetWinner {}.data(),
+        }
+    }
+
+    // the winner's prize ATA is created by the program if it's missing
+    pub fn claim_prize(&self, payer: &Pubkey, winner: &Pubkey, ticket: u64) -> Instruction {
+        self.claim_prize_of(payer, winner, ticket, &self.mint_prize)
+    }
+
+    // claims whatever `mint` the ticket won, a tier or a bundle nft
+    pub fn claim_prize_of(&self, payer: &Pubkey, winner: &Pubkey, ticket: u64, mint: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::ClaimPrize {
+            payer: *payer,
+            winner: *winner,
+            mint_prize: *mint,
+            raffle: self.raffle,
+            token_prize: token_address(winner, mint, &self.token_program),
+            escrow_token_prize: token_address(&self.raffle, mint, &self.token_program),
+            fixed_raffle: self.tickets,
+            associated_token: associated_token::ID,
+            system_program: anchor_lang::system_program::ID,
+            token_program: self.token_program,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::ClaimPrize { ticket }.data(),
+        }
+    }
+
+    // locks, reveals and draws until every winner is in
+    pub async fn draw(&self, env: &mut TestEnv, secret: [u8; 32]) -> RaffleAccount {
+        let account: RaffleAccount = env.account(&self.raffle).await;
+        let creator = self.creator.insecure_clone();
+
+        env.end_raffle(account.end).await;
+        env.process(&[self.lock(&creator.pubkey())], &[&creator]).await.unwrap();
+        env.pass_entropy_delay().await;
+        env.process(&[self.reveal(secret)], &[&creator]).await.unwrap();
+
+        loop {
+            let account: RaffleAccount = env.account(&self.raffle).await;
+
+            if account.winners_selected {
+                return account;
+            }
+
+            env.process(&[self.set_winner()], &[&creator]).await.unwrap();
+        }
+    }
+
+    pub fn randomness_request(&self) -> Pubkey {
+        randomness_address(&self.raffle)
+    }
+
+    pub fn request_randomness(&self, payer: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::RequestRandomness {
+            payer: *payer,
+            raffle: self.raffle,
+            request: self.randomness_request(),
+            system_program: anchor_lang::system_program::ID,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::RequestRandomness {}.data(),
+        }
+    }
+
+    // has to come right after the ed25519 instruction carrying the proof
+    pub fn fulfill_randomness(&self, oracle: &Pubkey, proof: [u8; 64]) -> Instruction {
+        let accounts = raffler_anchor::accounts::FulfillRandomness {
+            oracle: *oracle,
+            request: self.randomness_request(),
+            instructions: anchor_lang::solana_program::sysvar::instructions::ID,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::FulfillRandomness { proof }.data(),
+        }
+    }
+
+    // the creator pays the last unpaid winner, whose prize ATA has to exist already
+    pub fn draw_winner(&self, recipient: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::DrawWinner {
+            payer: self.creator.pubkey(),
+            recipient: *recipient,
+            mint_cost: self.mint_cost,
+            mint_prize: self.mint_prize,
+            raffle: self.raffle,
+            token_prize: token_address(recipient, &self.mint_prize, &self.token_program),
+            escrow_token_prize: self.escrow_prize,
+            token_program: self.token_program,
+            fixed_raffle: self.tickets,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::DrawWinner {}.data(),
+        }
+    }
+
+    pub fn set_fee(&self, env: &TestEnv, fee_bps: u16) -> Instruction {
+        let accounts = raffler_anchor::accounts::SetRaffleFee {
+            payer: env.admin.pubkey(),
+            raffle: self.raffle,
+            config: config_address(),
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::SetRaffleFee { fee_bps }.data(),
+        }
+    }
+
+    // the creator funds the tier from their ATA for `mint`, the program creates the escrow
+    pub fn add_tier(&self, mint: &Pubkey, data: PrizeTierData) -> Instruction {
+        let accounts = raffler_anchor::accounts::AddPrizeTier {
+            payer: self.creator.pubkey(),
+            raffle: self.raffle,
+            mint_prize: *mint,
+            token_prize: ata(&self.creator.pubkey(), mint),
+            escrow_token_prize: ata(&self.raffle, mint),
+            associated_token: associated_token::ID,
+            system_program: anchor_lang::system_program::ID,
+            token_program: spl_token::ID,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::AddPrizeTier { data }.data(),
+        }
+    }
+
+    pub fn close_tier(&self, env: &TestEnv, mint: &Pubkey, tier: u8, force_close: bool) -> Instruction {
+        let accounts = raffler_anchor::accounts::ClosePrizeTier {
+            payer: if force_close { env.admin.pubkey() } else { self.creator.pubkey() },
+            raffle: self.raffle,
+            mint_prize: *mint,
+            token_prize: ata(&self.creator.pubkey(), mint),
+            escrow_token_prize: ata(&self.raffle, mint),
+            fee_recipient: env.fee_recipient.pubkey(),
+            config: config_address(),
+            token_program: spl_token::ID,
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::ClosePrizeTier { tier, force_close }.data(),
+        }
+    }
+
+    pub fn close_entry(&self, buyer: &Pubkey) -> Instruction {
+        let accounts = raffler_anchor::accounts::CloseEntry {
+            payer: *buyer,
+            raffle: self.raffle,
+            buyer_entry: entry_address(&self.raffle, buyer),
+            config: config_address(),
+        };
+
+        Instruction {
+            program_id: raffler_anchor::ID,
+            accounts: accounts.to_account_metas(None),
+            data: raffler_anchor::instruction::CloseEntry {}.data(),
+        }
+    }
+}
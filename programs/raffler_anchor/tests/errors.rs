@@ -0,0 +1,554 @@
+mod common;
+
+use {
+    common::*,
+    raffler_anchor::{allowlist::allowlist_leaf, error::CustomError, metadata::*, randomness::*, state::*},
+    anchor_lang::{
+        prelude::Pubkey,
+        solana_program::{hash::{hashv, Hash}, slot_hashes::{SlotHashes, MAX_ENTRIES}},
+    },
+    solana_sdk::{signature::Keypair, signer::Signer},
+};
+
+async fn create_error(env: &mut TestEnv, data: CreateRaffleData, code: CustomError) {
+    assert_error(TestRaffle::try_create(env, data, RAFFLE_ENTRY_OFFSET).await.map(|_| ()), code);
+}
+
+#[tokio::test]
+async fn create_raffle_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+
+    create_error(&mut env, CreateRaffleData { price: 0, ..drawn_data(now) }, CustomError::InputError).await;
+    create_error(&mut env, CreateRaffleData { native: true, burn: true, ..drawn_data(now) }, CustomError::InputError).await;
+    create_error(&mut env, CreateRaffleData { source: RandomnessSource::Oracle, ..drawn_data(now) }, CustomError::InputError).await;
+    create_error(&mut env, CreateRaffleData { prize_quantity: 3, per_win: 2, ..drawn_data(now) }, CustomError::DivisibleError).await;
+    create_error(&mut env, CreateRaffleData { end: now + 60 * 60 * 24 * 15, ..drawn_data(now) }, CustomError::TimeError).await;
+    create_error(&mut env, CreateRaffleData { end: i64::MAX, ..drawn_data(now) }, CustomError::TimeError).await;
+    create_error(&mut env, CreateRaffleData { cost_decimals: 10, ..drawn_data(now) }, CustomError::DecimalError).await;
+    create_error(&mut env, CreateRaffleData { prize_decimals: 1, ..drawn_data(now) }, CustomError::DecimalError).await;
+
+    let result = TestRaffle::try_create(&mut env, drawn_data(now), RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE).await;
+    assert_error(result.map(|_| ()), CustomError::FixedError);
+
+    env.update_config(|config| config.paused = true).await.unwrap();
+    create_error(&mut env, drawn_data(now), CustomError::Paused).await;
+
+    env.update_config(|config| config.paused = false).await.unwrap();
+    TestRaffle::create(&mut env, drawn_data(now)).await;
+}
+
+#[tokio::test]
+async fn prize_metadata_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let collection = Pubkey::new_unique();
+    let verified = Some(MetadataCollection { verified: true, key: collection });
+    let metadata_data = |uri: &str| CreateRaffleData { verify_metadata: true, nft_uri: uri.to_string(), ..drawn_data(now) };
+
+    // the mint is filled in by try_create_with
+    let metadata = mint_metadata(&Pubkey::default(), "https://prize.json", verified.clone());
+    let result = TestRaffle::try_create_with(&mut env, metadata_data("https://other.json"), RAFFLE_ENTRY_OFFSET, Some(metadata)).await;
+    assert_error(result.map(|_| ()), CustomError::MetadataMismatch);
+
+    let unverified = Some(MetadataCollection { verified: false, key: collection });
+    let metadata = mint_metadata(&Pubkey::default(), "https://prize.json", unverified);
+    let result = TestRaffle::try_create_with(&mut env, metadata_data("https://prize.json"), RAFFLE_ENTRY_OFFSET, Some(metadata)).await;
+    assert_error(result.map(|_| ()), CustomError::UnverifiedCollection);
+
+    let metadata = mint_metadata(&Pubkey::default(), "https://prize.json", verified);
+    let raffle = TestRaffle::try_create_with(&mut env, metadata_data("https://prize.json"), RAFFLE_ENTRY_OFFSET, Some(metadata)).await.unwrap();
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    assert_eq!(account.collection, collection);
+}
+
+#[tokio::test]
+async fn buy_ticket_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { max_tickets_per_wallet: 4, ..drawn_data(now) }).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+    let creator = raffle.creator.insecure_clone();
+
+    assert_error(env.process(&[raffle.buy(&creator.pubkey(), 1)], &[&creator]).await, CustomError::InputError);
+    assert_error(env.process(&[raffle.buy(&buyer.pubkey(), 1201)], &[&buyer]).await, CustomError::TooMany);
+
+    env.process(&[raffle.buy(&buyer.pubkey(), 3)], &[&buyer]).await.unwrap();
+    assert_error(env.process(&[raffle.buy(&buyer.pubkey(), 2)], &[&buyer]).await, CustomError::WalletLimit);
+
+    env.update_config(|config| config.paused = true).await.unwrap();
+    assert_error(env.process(&[raffle.buy(&buyer.pubkey(), 1)], &[&buyer]).await, CustomError::Paused);
+    env.update_config(|config| config.paused = false).await.unwrap();
+
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+    assert_error(env.process(&[raffle.buy(&buyer.pubkey(), 1)], &[&buyer]).await, CustomError::RaffleCancelled);
+
+    // not open yet
+    let early = TestRaffle::create(&mut env, CreateRaffleData { start: now + 100, ..drawn_data(now) }).await;
+    assert_error(env.process(&[early.buy(&buyer.pubkey(), 1)], &[&buyer]).await, CustomError::TooEarly);
+
+    // sold out
+    let fixed = TestRaffle::create(&mut env, CreateRaffleData { fixed: true, max_entries: 2, ..drawn_data(now) }).await;
+    assert_error(env.process(&[fixed.buy(&buyer.pubkey(), 3)], &[&buyer]).await, CustomError::NotEnough);
+
+    // and over
+    env.warp_to(drawn_data(now).end + 1).await;
+    assert_error(env.process(&[fixed.buy(&buyer.pubkey(), 1)], &[&buyer]).await, CustomError::TooLate);
+}
+
+#[tokio::test]
+async fn allowlist_and_gate_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (listed, other, outsider) = (env.funded_keypair(1_000_000_000).await, env.funded_keypair(1_000_000_000).await, env.funded_keypair(1_000_000_000).await);
+
+    // two leaves, hashed in sorted order like verify_allowlist does
+    let (a, b) = (allowlist_leaf(&listed.pubkey(), 3), allowlist_leaf(&other.pubkey(), 0));
+    let root = if a <= b { hashv(&[&a, &b]) } else { hashv(&[&b, &a]) }.to_bytes();
+
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { allowlist: Some(root), ..drawn_data(now) }).await;
+
+    assert_error(env.process(&[raffle.buy_with(&outsider.pubkey(), 1, 0, vec![a], None, None)], &[&outsider]).await, CustomError::NotAllowlisted);
+    assert_error(env.process(&[raffle.buy_with(&listed.pubkey(), 1, 5, vec![b], None, None)], &[&listed]).await, CustomError::NotAllowlisted);
+
+    env.process(&[raffle.buy_with(&listed.pubkey(), 2, 3, vec![b], None, None)], &[&listed]).await.unwrap();
+    assert_error(env.process(&[raffle.buy_with(&listed.pubkey(), 2, 3, vec![b], None, None)], &[&listed]).await, CustomError::WalletLimit);
+    env.process(&[raffle.buy_with(&other.pubkey(), 10, 0, vec![a], None, None)], &[&other]).await.unwrap();
+
+    // holders of at least two gate tokens only
+    let gate_mint = env.create_mint(0).await;
+    let gated = TestRaffle::create(&mut env, CreateRaffleData { gate: TicketGate::Token { mint: gate_mint, amount: 2 }, ..drawn_data(now) }).await;
+    let holder = env.funded_keypair(1_000_000_000).await;
+    let gate_token = env.fund_tokens(&holder.pubkey(), &gate_mint, 1).await;
+
+    assert_error(env.process(&[gated.buy(&holder.pubkey(), 1)], &[&holder]).await, CustomError::GateFailed);
+    assert_error(env.process(&[gated.buy_with(&holder.pubkey(), 1, 0, vec![], Some(gate_token), None)], &[&holder]).await, CustomError::GateFailed);
+
+    env.mint_to(&gate_mint, &gate_token, 1).await;
+    env.process(&[gated.buy_with(&holder.pubkey(), 1, 0, vec![], Some(gate_token), None)], &[&holder]).await.unwrap();
+}
+
+#[tokio::test]
+async fn collection_gate_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let collection = Pubkey::new_unique();
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { gate: TicketGate::Collection { collection }, ..drawn_data(now) }).await;
+    let holder = env.funded_keypair(1_000_000_000).await;
+
+    // one nft per metadata, only the first is a verified member
+    let mut nfts = vec![];
+
+    for (verified, key) in [(true, collection), (false, collection), (true, Pubkey::new_unique())] {
+        let mint = env.create_mint(0).await;
+        let token = env.fund_tokens(&holder.pubkey(), &mint, 1).await;
+
+        env.set_metadata(&mint_metadata(&mint, "", Some(MetadataCollection { verified, key })));
+        nfts.push((token, metadata_address(&mint)));
+    }
+
+    let [(member, member_metadata), (unverified, unverified_metadata), (foreign, foreign_metadata)] = nfts[..] else { unreachable!() };
+
+    assert_error(env.process(&[raffle.buy(&holder.pubkey(), 1)], &[&holder]).await, CustomError::GateFailed);
+    assert_error(env.process(&[raffle.buy_with(&holder.pubkey(), 1, 0, vec![], Some(member), None)], &[&holder]).await, CustomError::GateFailed);
+    assert_error(env.process(&[raffle.buy_with(&holder.pubkey(), 1, 0, vec![], Some(unverified), Some(unverified_metadata))], &[&holder]).await, CustomError::GateFailed);
+    assert_error(env.process(&[raffle.buy_with(&holder.pubkey(), 1, 0, vec![], Some(foreign), Some(foreign_metadata))], &[&holder]).await, CustomError::GateFailed);
+
+    // the member's metadata doesn't vouch for another nft
+    assert_error(env.process(&[raffle.buy_with(&holder.pubkey(), 1, 0, vec![], Some(foreign), Some(member_metadata))], &[&holder]).await, CustomError::GateFailed);
+
+    env.process(&[raffle.buy_with(&holder.pubkey(), 1, 0, vec![], Some(member), Some(member_metadata))], &[&holder]).await.unwrap();
+
+    assert_eq!(env.account::<RaffleAccount>(&raffle.raffle).await.tickets_purchased, 1);
+}
+
+#[tokio::test]
+async fn lock_and_reveal_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (raffle, _) = sold(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+
+    assert_error(env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await, CustomError::RaffleGoing);
+    assert_error(env.process(&[raffle.reveal(SECRET)], &[&creator]).await, CustomError::NotLocked);
+    assert_error(env.process(&[raffle.set_winner()], &[&creator]).await, CustomError::NotLocked);
+
+    env.end_raffle(drawn_data(now).end).await;
+    env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    assert_error(env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await, CustomError::AlreadyLocked);
+    assert_error(env.process(&[raffle.set_winner()], &[&creator]).await, CustomError::RevealPending);
+    assert_error(env.process(&[raffle.reveal([8; 32])], &[&creator]).await, CustomError::BadReveal);
+
+    // the slot it mixes in hasn't happened yet
+    assert_error(env.process(&[raffle.reveal(SECRET)], &[&creator]).await, CustomError::RevealPending);
+
+    // once locked the creator can't back out anymore
+    assert_error(env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await, CustomError::InputError);
+
+    env.pass_entropy_delay().await;
+    env.process(&[raffle.reveal(SECRET)], &[&creator]).await.unwrap();
+
+    // anyone can crank the draw once the seed is in
+    let stranger = env.funded_keypair(1_000_000_000).await;
+
+    while !env.account::<RaffleAccount>(&raffle.raffle).await.winners_selected {
+        env.process(&[raffle.set_winner_by(&stranger.pubkey())], &[&stranger]).await.unwrap();
+    }
+
+    assert_error(env.process(&[raffle.set_winner()], &[&creator]).await, CustomError::WinnersAlreadyPicked);
+
+    let admin = env.admin.insecure_clone();
+    assert_error(env.process(&[raffle.cancel(&admin.pubkey())], &[&admin]).await, CustomError::WinnersAlreadyPicked);
+}
+
+#[tokio::test]
+async fn undersold_raffles_are_refunded() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { min_tickets: 3, ..drawn_data(now) }).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+    let creator = raffle.creator.insecure_clone();
+
+    env.process(&[raffle.buy(&buyer.pubkey(), 1)], &[&buyer]).await.unwrap();
+    env.end_raffle(drawn_data(now).end).await;
+
+    assert_error(env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await, CustomError::MinTicketsNotReached);
+    assert_error(env.process(&[raffle.claim_refund(&buyer.pubkey())], &[&buyer]).await, CustomError::NotCancelled);
+
+    // anyone can cancel an undersold raffle
+    env.process(&[raffle.cancel(&buyer.pubkey())], &[&buyer]).await.unwrap();
+
+    assert_error(env.process(&[raffle.cancel(&buyer.pubkey())], &[&buyer]).await, CustomError::RaffleCancelled);
+    assert_error(env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await, CustomError::RaffleCancelled);
+    assert_error(env.process(&[raffle.close(&env, false)], &[&creator]).await, CustomError::RefundsPending);
+
+    env.process(&[raffle.claim_refund(&buyer.pubkey())], &[&buyer]).await.unwrap();
+    assert_error(env.process(&[raffle.claim_refund(&buyer.pubkey())], &[&buyer]).await, CustomError::NothingToRefund);
+
+    // a wallet that never bought has no entry to refund from
+    let stranger = env.funded_keypair(1_000_000_000).await;
+    assert!(env.process(&[raffle.claim_refund(&stranger.pubkey())], &[&stranger]).await.is_err());
+
+    assert_error(env.process(&[raffle.close_entry(&buyer.pubkey())], &[&buyer]).await, CustomError::RaffleOpen);
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+    env.process(&[raffle.close_entry(&buyer.pubkey())], &[&buyer]).await.unwrap();
+}
+
+#[tokio::test]
+async fn too_few_tickets_for_every_winner() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+    let creator = raffle.creator.insecure_clone();
+
+    // two winners and no win_multiple, one ticket can't fill both slots
+    env.process(&[raffle.buy(&buyer.pubkey(), 1)], &[&buyer]).await.unwrap();
+    env.end_raffle(drawn_data(now).end).await;
+
+    assert_error(env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await, CustomError::MinTicketsNotReached);
+
+    env.process(&[raffle.cancel(&buyer.pubkey())], &[&buyer]).await.unwrap();
+    env.process(&[raffle.claim_refund(&buyer.pubkey())], &[&buyer]).await.unwrap();
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+}
+
+#[tokio::test]
+async fn reveal_after_the_slot_hash_is_gone() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (raffle, buyers) = sold(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+
+    env.end_raffle(drawn_data(now).end).await;
+    env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    // a full sysvar that has moved past the lock
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    let newest = account.lock_slot + ENTROPY_DELAY + MAX_ENTRIES as u64;
+    let entries: Vec<_> = (0..MAX_ENTRIES as u64).map(|back| (newest - back, Hash::new_unique())).collect();
+    env.context.set_sysvar(&SlotHashes::new(&entries));
+
+    assert_error(env.process(&[raffle.reveal(SECRET)], &[&creator]).await, CustomError::EntropyExpired);
+
+    // the draw can't happen anymore, at the timeout anyone sends it to refunds
+    env.warp_to(account.locked_at + REVEAL_TIMEOUT).await;
+    env.process(&[raffle.set_winner_by(&buyers[0].pubkey())], &[&buyers[0]]).await.unwrap();
+
+    assert!(env.account::<RaffleAccount>(&raffle.raffle).await.refunding);
+    assert_error(env.process(&[raffle.reveal(SECRET)], &[&creator]).await, CustomError::RaffleCancelled);
+}
+
+#[tokio::test]
+async fn a_recorded_slot_hash_outlives_the_sysvar() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (raffle, buyers) = sold(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let stranger = env.funded_keypair(1_000_000_000).await;
+
+    assert_error(env.process(&[raffle.record_slot_hash(&stranger.pubkey())], &[&stranger]).await, CustomError::NotLocked);
+
+    env.end_raffle(drawn_data(now).end).await;
+    env.process(&[raffle.lock(&buyers[0].pubkey())], &[&buyers[0]]).await.unwrap();
+
+    assert_error(env.process(&[raffle.record_slot_hash(&stranger.pubkey())], &[&stranger]).await, CustomError::RevealPending);
+
+    // anyone can keep the hash once its slot has passed, but only once
+    env.pass_entropy_delay().await;
+    env.process(&[raffle.record_slot_hash(&stranger.pubkey())], &[&stranger]).await.unwrap();
+    assert_error(env.process(&[raffle.record_slot_hash(&stranger.pubkey())], &[&stranger]).await, CustomError::InputError);
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    assert_ne!(account.slot_hash, [0; 32]);
+
+    // the sysvar moves on, the reveal still has the rest of the timeout
+    let newest = account.lock_slot + ENTROPY_DELAY + MAX_ENTRIES as u64;
+    let entries: Vec<_> = (0..MAX_ENTRIES as u64).map(|back| (newest - back, Hash::new_unique())).collect();
+    env.context.set_sysvar(&SlotHashes::new(&entries));
+
+    env.warp_to(account.locked_at + REVEAL_TIMEOUT - 60).await;
+    env.process(&[raffle.reveal(SECRET)], &[&creator]).await.unwrap();
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    assert_eq!(account.seed, reveal_seed(&SECRET, &account.slot_hash));
+}
+
+#[tokio::test]
+async fn set_winner_on_a_cancelled_raffle() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (raffle, _) = sold(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let admin = env.admin.insecure_clone();
+
+    env.end_raffle(drawn_data(now).end).await;
+    env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    // the admin can still cancel a locked raffle
+    env.process(&[raffle.cancel(&admin.pubkey())], &[&admin]).await.unwrap();
+
+    assert_error(env.process(&[raffle.set_winner()], &[&creator]).await, CustomError::RaffleCancelled);
+}
+
+#[tokio::test]
+async fn oracle_proof_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let oracle = env.funded_keypair(1_000_000_000).await;
+    let data = CreateRaffleData { source: RandomnessSource::Oracle, oracle: oracle.pubkey(), ..drawn_data(now) };
+    let (raffle, _) = sold(&mut env, data).await;
+    let creator = raffle.creator.insecure_clone();
+
+    env.end_raffle(raffle_data(now).end).await;
+    env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await.unwrap();
+    env.process(&[raffle.request_randomness(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    // oracle raffles don't take a secret
+    assert_error(env.process(&[raffle.reveal(SECRET)], &[&creator]).await, CustomError::InputError);
+
+    let request: RandomnessRequest = env.account(&raffle.randomness_request()).await;
+
+    // no ed25519 instruction at all
+    assert_error(env.process(&[raffle.fulfill_randomness(&oracle.pubkey(), [1; 64])], &[&oracle]).await, CustomError::BadProof);
+
+    // a valid signature, but not the oracle's
+    let (ed25519, proof) = ed25519_instruction(&Keypair::new(), &request.alpha);
+    assert_error(env.process(&[ed25519, raffle.fulfill_randomness(&oracle.pubkey(), proof)], &[&oracle]).await, CustomError::BadProof);
+
+    // the oracle's, over the wrong message
+    let (ed25519, proof) = ed25519_instruction(&oracle, &[0; 32]);
+    assert_error(env.process(&[ed25519, raffle.fulfill_randomness(&oracle.pubkey(), proof)], &[&oracle]).await, CustomError::BadProof);
+}
+
+#[tokio::test]
+async fn payout_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (raffle, buyers) = sold(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let account = raffle.draw(&mut env, SECRET).await;
+    let buyer_of = |ticket: u64| if ticket < 3 { buyers[0].insecure_clone() } else { buyers[1].insecure_clone() };
+
+    let loser = (0..5).find(|ticket| !account.winners.contains(ticket)).unwrap();
+    let holder = buyer_of(loser);
+    assert_error(env.process(&[raffle.claim_prize(&holder.pubkey(), &holder.pubkey(), loser)], &[&holder]).await, CustomError::NotWinner);
+
+    // a winning ticket claimed for someone who doesn't hold it
+    let winner = buyer_of(account.winners[0]);
+    let stranger = env.funded_keypair(1_000_000_000).await;
+    let steal = raffle.claim_prize(&stranger.pubkey(), &stranger.pubkey(), account.winners[0]);
+    assert_error(env.process(&[steal], &[&stranger]).await, CustomError::NotWinner);
+
+    env.process(&[raffle.claim_prize(&winner.pubkey(), &winner.pubkey(), account.winners[0])], &[&winner]).await.unwrap();
+    assert_error(env.process(&[raffle.claim_prize(&winner.pubkey(), &winner.pubkey(), account.winners[0])], &[&winner]).await, CustomError::AlreadyClaimed);
+
+    // one of two paid
+    assert_error(env.process(&[raffle.close(&env, false)], &[&creator]).await, CustomError::CantScam);
+
+    let last = buyer_of(account.winners[1]).pubkey();
+
+    if env.context.banks_client.get_account(ata(&last, &raffle.mint_prize)).await.unwrap().is_none() {
+        env.create_ata(&last, &raffle.mint_prize).await;
+    }
+
+    env.process(&[raffle.draw_winner(&last)], &[&creator]).await.unwrap();
+    assert_error(env.process(&[raffle.draw_winner(&last)], &[&creator]).await, CustomError::AllWinnersPaid);
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+}
+
+#[tokio::test]
+async fn fee_and_config_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let admin = env.admin.insecure_clone();
+
+    assert_error(env.process(&[raffle.set_fee(&env, 501)], &[&admin]).await, CustomError::FeeTooHigh);
+    env.process(&[raffle.set_fee(&env, 500)], &[&admin]).await.unwrap();
+
+    // the fee is locked in once anyone has paid
+    let buyer = env.funded_keypair(1_000_000_000).await;
+    env.process(&[raffle.buy(&buyer.pubkey(), 1)], &[&buyer]).await.unwrap();
+    assert_error(env.process(&[raffle.set_fee(&env, 0)], &[&admin]).await, CustomError::RaffleStarted);
+
+    assert_error(env.update_config(|config| config.proceeds_fee_bps = config.max_proceeds_fee_bps + 1).await, CustomError::InputError);
+    assert_error(env.update_config(|config| config.fee_bps = MAX_BPS + 1).await, CustomError::InputError);
+}
+
+#[tokio::test]
+async fn prize_tier_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let tier_mint = env.create_mint(0).await;
+
+    env.fund_tokens(&creator.pubkey(), &tier_mint, 10).await;
+
+    assert_error(env.process(&[raffle.add_tier(&tier_mint, PrizeTierData { quantity: 3, per_win: 2, decimals: 0 })], &[&creator]).await, CustomError::DivisibleError);
+    assert_error(env.process(&[raffle.add_tier(&tier_mint, PrizeTierData { quantity: 2, per_win: 1, decimals: 1 })], &[&creator]).await, CustomError::DecimalError);
+    assert_error(env.process(&[raffle.add_tier(&raffle.mint_prize, PrizeTierData { quantity: 1, per_win: 1, decimals: 0 })], &[&creator]).await, CustomError::InputError);
+
+    env.process(&[raffle.add_tier(&tier_mint, PrizeTierData { quantity: 2, per_win: 1, decimals: 0 })], &[&creator]).await.unwrap();
+
+    // the raffle signs for the tier's escrow, so it can't close with the tier still open
+    assert_error(env.process(&[raffle.close(&env, false)], &[&creator]).await, CustomError::PrizeTiersOpen);
+
+    // nothing sold yet, but it still could
+    assert_error(env.process(&[raffle.close_tier(&env, &tier_mint, 1, false)], &[&creator]).await, CustomError::RaffleGoing);
+
+    let buyer = env.funded_keypair(1_000_000_000).await;
+    env.process(&[raffle.buy(&buyer.pubkey(), 1)], &[&buyer]).await.unwrap();
+
+    let other_mint = env.create_mint(0).await;
+    env.fund_tokens(&creator.pubkey(), &other_mint, 1).await;
+    assert_error(env.process(&[raffle.add_tier(&other_mint, PrizeTierData { quantity: 1, per_win: 1, decimals: 0 })], &[&creator]).await, CustomError::RaffleStarted);
+
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+    assert_error(env.process(&[raffle.add_tier(&other_mint, PrizeTierData { quantity: 1, per_win: 1, decimals: 0 })], &[&creator]).await, CustomError::RaffleStarted);
+    assert_error(env.process(&[raffle.close_tier(&env, &tier_mint, 1, false)], &[&creator]).await, CustomError::RefundsPending);
+
+    env.process(&[raffle.claim_refund(&buyer.pubkey())], &[&buyer]).await.unwrap();
+    env.process(&[raffle.close_tier(&env, &tier_mint, 1, false)], &[&creator]).await.unwrap();
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    assert_eq!(env.token_balance(&ata(&creator.pubkey(), &tier_mint)).await, 10);
+}
+
+#[tokio::test]
+async fn raffle_account_size_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+
+    // every winner takes 9 bytes, two thousand of them can't fit
+    create_error(&mut env, CreateRaffleData { prize_quantity: 2000, ..drawn_data(now) }, CustomError::RaffleTooLarge).await;
+
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { prize_quantity: 1000, ..drawn_data(now) }).await;
+    let creator = raffle.creator.insecure_clone();
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(env.data(&raffle.raffle).await.len(), account.space());
+
+    let tier_mint = env.create_mint(0).await;
+    env.fund_tokens(&creator.pubkey(), &tier_mint, 100).await;
+
+    assert_error(env.process(&[raffle.add_tier(&tier_mint, PrizeTierData { quantity: 100, per_win: 1, decimals: 0 })], &[&creator]).await, CustomError::RaffleTooLarge);
+
+    env.process(&[raffle.add_tier(&tier_mint, PrizeTierData { quantity: 60, per_win: 1, decimals: 0 })], &[&creator]).await.unwrap();
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert!(account.space() <= MAX_RAFFLE_SPACE);
+    assert_eq!(env.data(&raffle.raffle).await.len(), account.space());
+}
+
+#[tokio::test]
+async fn bundle_checks() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+
+    // enough winners that the account already had to grow, every nft grows it again
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { prize_quantity: 70, ..drawn_data(now) }).await;
+    let creator = raffle.creator.insecure_clone();
+
+    let mut bundle = vec![];
+
+    for _ in 0..MAX_BUNDLE_NFTS + 1 {
+        let mint = env.create_mint(0).await;
+        env.fund_tokens(&creator.pubkey(), &mint, 1).await;
+        bundle.push(mint);
+    }
+
+    // more than one of the mint isn't an nft, the tier's own mint can't be bundled either
+    let fungible = env.create_mint(0).await;
+    env.fund_tokens(&creator.pubkey(), &fungible, 2).await;
+
+    assert_error(env.process(&[raffle.add_bundle(&[fungible])], &[&creator]).await, CustomError::InputError);
+    assert_error(env.process(&[raffle.add_bundle(&[raffle.mint_prize])], &[&creator]).await, CustomError::InputError);
+    assert_error(env.process(&[raffle.add_bundle(&[])], &[&creator]).await, CustomError::InputError);
+
+    env.process(&[raffle.add_bundle(&bundle[..5])], &[&creator]).await.unwrap();
+    env.process(&[raffle.add_bundle(&bundle[5..10])], &[&creator]).await.unwrap();
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(account.bundle, bundle[..10]);
+    assert_eq!(env.data(&raffle.raffle).await.len(), account.space());
+    assert_error(env.process(&[raffle.add_bundle(&bundle[10..])], &[&creator]).await, CustomError::InputError);
+
+    let buyer = env.funded_keypair(1_000_000_000).await;
+    env.process(&[raffle.buy(&buyer.pubkey(), 1)], &[&buyer]).await.unwrap();
+
+    let late = env.create_mint(0).await;
+    env.fund_tokens(&creator.pubkey(), &late, 1).await;
+    assert_error(env.process(&[raffle.add_bundle(&[late])], &[&creator]).await, CustomError::RaffleStarted);
+}
+
+#[tokio::test]
+async fn cancelled_raffles_take_no_tiers() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let tier_mint = env.create_mint(0).await;
+
+    env.fund_tokens(&creator.pubkey(), &tier_mint, 1).await;
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    assert_error(env.process(&[raffle.add_tier(&tier_mint, PrizeTierData { quantity: 1, per_win: 1, decimals: 0 })], &[&creator]).await, CustomError::RaffleCancelled);
+}
+
+#[tokio::test]
+async fn close_needs_a_payout_first() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (raffle, _) = sold(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+
+    // tickets sold and nothing paid out
+    assert_error(env.process(&[raffle.close(&env, false)], &[&creator]).await, CustomError::RaffleStarted);
+}
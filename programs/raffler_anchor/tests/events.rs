@@ -0,0 +1,367 @@
+mod common;
+
+use {
+    common::*,
+    raffler_anchor::{events::*, randomness::commitment, state::*},
+    solana_sdk::{instruction::Instruction, signature::Keypair, signer::Signer},
+};
+
+async fn emitted<T: anchor_lang::Event>(env: &mut TestEnv, instructions: &[Instruction], signers: &[&Keypair]) -> Vec<T> {
+    let (result, logs) = env.process_with_logs(instructions, signers).await;
+    result.unwrap();
+
+    events(&logs)
+}
+
+#[tokio::test]
+async fn create_describes_the_raffle() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let data = CreateRaffleData {
+        nonce: 3,
+        max_tickets_per_wallet: 5,
+        commitment: commitment(&SECRET),
+        ..raffle_data(now)
+    };
+
+    let raffle = TestRaffle::create(&mut env, data).await;
+    let created = events::<RaffleCreated>(&raffle.logs);
+
+    assert_eq!(created.len(), 1);
+
+    let event = &created[0];
+
+    assert_eq!(event.raffle, raffle.raffle);
+    assert_eq!(event.owner, raffle.creator.pubkey());
+    assert_eq!(event.mint, raffle.mint_cost);
+    assert_eq!(event.prize, raffle.mint_prize);
+    assert_eq!(event.tickets, raffle.tickets);
+    assert_eq!(event.nonce, 3);
+    assert_eq!(event.price, PRICE);
+    assert_eq!(event.prize_quantity, 1);
+    assert_eq!(event.max_entries, u64::MAX);
+    assert_eq!(event.max_tickets_per_wallet, 5);
+    assert_eq!(event.start, now - 60);
+    assert_eq!(event.commitment, commitment(&SECRET));
+    assert_eq!(event.gate, TicketGate::Open);
+    assert_eq!(event.source, RandomnessSource::SlotHash);
+    assert!(event.native && !event.fixed && !event.burn);
+}
+
+#[tokio::test]
+async fn purchases_carry_their_ticket_range() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let a = env.funded_keypair(1_000_000_000).await;
+    let b = env.funded_keypair(1_000_000_000).await;
+
+    // rebuilt from the events alone
+    let mut owners: Vec<(solana_sdk::pubkey::Pubkey, u64, u64)> = vec![];
+
+    for (buyer, amount) in [(&a, 2), (&b, 3), (&a, 1), (&a, 4)] {
+        let purchased = emitted::<TicketsPurchased>(&mut env, &[raffle.buy(&buyer.pubkey(), amount)], &[buyer]).await;
+
+        assert_eq!(purchased.len(), 1);
+
+        let event = &purchased[0];
+
+        assert_eq!(event.raffle, raffle.raffle);
+        assert_eq!(event.buyer, buyer.pubkey());
+        assert_eq!(event.amount, amount);
+        assert_eq!(event.cost, PRICE * amount);
+        assert_eq!(event.tickets_purchased, event.first_ticket + amount);
+
+        owners.push((event.buyer, event.first_ticket, event.tickets_purchased));
+    }
+
+    assert_eq!(owners, vec![(a.pubkey(), 0, 2), (b.pubkey(), 2, 5), (a.pubkey(), 5, 6), (a.pubkey(), 6, 10)]);
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    assert_eq!(account.tickets_purchased, 10);
+    assert_eq!(account.unique_entries, 2);
+}
+
+#[tokio::test]
+async fn purchases_flag_new_buyers_and_rent() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let a = env.funded_keypair(1_000_000_000).await;
+
+    let first = emitted::<TicketsPurchased>(&mut env, &[raffle.buy(&a.pubkey(), 1)], &[&a]).await;
+    let second = emitted::<TicketsPurchased>(&mut env, &[raffle.buy(&a.pubkey(), 2)], &[&a]).await;
+
+    assert!(first[0].new_buyer && first[0].rent_paid > 0);
+    assert_eq!(first[0].unique_entries, 1);
+
+    // extending the same run is free and the wallet is already counted
+    assert!(!second[0].new_buyer);
+    assert_eq!(second[0].rent_paid, 0);
+    assert_eq!(second[0].unique_entries, 1);
+
+    let entry: BuyerEntry = env.account(&entry_address(&raffle.raffle, &a.pubkey())).await;
+    assert_eq!(entry.rent_paid, first[0].rent_paid);
+}
+
+#[tokio::test]
+async fn draw_claim_and_close() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (raffle, buyers) = sold(&mut env, CreateRaffleData { win_multiple: true, ..drawn_data(now) }).await;
+    let end = raffle_data(now).end;
+    let creator = raffle.creator.insecure_clone();
+
+    env.end_raffle(end).await;
+
+    let locked = emitted::<RaffleLocked>(&mut env, &[raffle.lock(&buyers[0].pubkey())], &[&buyers[0]]).await;
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(locked.len(), 1);
+    assert_eq!((locked[0].raffle, locked[0].locked_by), (raffle.raffle, buyers[0].pubkey()));
+    assert_eq!((locked[0].lock_slot, locked[0].locked_at), (account.lock_slot, account.locked_at));
+    assert_eq!(locked[0].tickets_purchased, 5);
+
+    env.pass_entropy_delay().await;
+
+    let revealed = emitted::<SeedRevealed>(&mut env, &[raffle.reveal(SECRET)], &[&creator]).await;
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(revealed.len(), 1);
+    assert_eq!(revealed[0].source, RandomnessSource::SlotHash);
+    assert_eq!((revealed[0].slot_hash, revealed[0].seed), (account.slot_hash, account.seed));
+
+    let mut selected = vec![];
+
+    while selected.len() < 2 {
+        selected.extend(emitted::<WinnerSelected>(&mut env, &[raffle.set_winner()], &[&creator]).await);
+    }
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    for (slot, event) in selected.iter().enumerate() {
+        let buyer = buyer_of(&buyers, event.ticket).pubkey();
+
+        assert_eq!(event.raffle, raffle.raffle);
+        assert_eq!(event.slot as usize, slot);
+        assert_eq!(event.ticket, account.winners[slot]);
+        assert_eq!(event.buyer, buyer);
+        assert_eq!(event.mint, raffle.mint_prize);
+        assert_eq!(event.seed, account.seed);
+        assert_eq!(event.tickets_purchased, 5);
+        assert_eq!(event.winners_selected, slot == 1);
+
+        // the event has everything needed to redo the draw
+        assert_eq!(raffler_anchor::randomness::winning_index(&event.seed, event.draw_nonce, event.tickets_purchased), Some(event.ticket));
+    }
+
+    for (slot, event) in selected.iter().enumerate() {
+        let winner = buyers.iter().find(|buyer| buyer.pubkey() == event.buyer).unwrap();
+        let sent = emitted::<PrizeSent>(&mut env, &[raffle.claim_prize(&winner.pubkey(), &winner.pubkey(), event.ticket)], &[winner]).await;
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].winner, winner.pubkey());
+        assert_eq!(sent[0].ticket, event.ticket);
+        assert_eq!(sent[0].mint, raffle.mint_prize);
+        assert_eq!(sent[0].amount, 1);
+        assert_eq!(sent[0].sent_out as usize, slot + 1);
+    }
+
+    let close = raffle.close(&env, false);
+    let closed = emitted::<RaffleClosed>(&mut env, &[close], &[&creator]).await;
+
+    assert_eq!(closed.len(), 1);
+
+    let event = &closed[0];
+
+    assert_eq!(event.raffle, raffle.raffle);
+    assert_eq!(event.closed_by, creator.pubkey());
+    assert!(!event.refunding);
+    assert_eq!(event.proceeds + event.fee, 5 * PRICE);
+    assert_eq!(event.burned, 0);
+    assert_eq!(event.returned, event.proceeds);
+    assert_eq!(event.prize_returned, 0);
+    assert_eq!(event.buyer_rent, account.buyer_rent);
+}
+
+#[tokio::test]
+async fn admin_force_close() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+
+    env.process(&[raffle.buy(&buyer.pubkey(), 2)], &[&buyer]).await.unwrap();
+
+    let creator = raffle.creator.insecure_clone();
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    // nobody refunded, the admin closes anyway
+    let admin = env.admin.insecure_clone();
+    let close = raffle.close(&env, true);
+    let (result, logs) = env.process_with_logs(&[close], &[&admin]).await;
+    result.unwrap();
+
+    let closed = events::<RaffleClosed>(&logs);
+    let forced = events::<RaffleForceClosed>(&logs);
+
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed[0].closed_by, admin.pubkey());
+    assert!(closed[0].refunding);
+    assert_eq!(closed[0].prize_returned, 1);
+
+    assert_eq!(forced.len(), 1);
+    assert_eq!(forced[0].raffle, raffle.raffle);
+    assert_eq!(forced[0].admin, admin.pubkey());
+    assert_eq!(forced[0].unrefunded_tickets, 2);
+    assert_eq!(forced[0].unpaid_winners, 0);
+
+    // a normal close has no force-close event
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { nonce: 1, ..raffle_data(now) }).await;
+    let close = raffle.close(&env, false);
+    let (result, logs) = env.process_with_logs(&[close], &[&raffle.creator.insecure_clone()]).await;
+    result.unwrap();
+
+    assert_eq!(events::<RaffleClosed>(&logs).len(), 1);
+    assert!(events::<RaffleForceClosed>(&logs).is_empty());
+}
+
+#[tokio::test]
+async fn cancel_and_refunds() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let (raffle, buyers) = sold(&mut env, raffle_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+
+    let cancelled = emitted::<RaffleCancelled>(&mut env, &[raffle.cancel(&creator.pubkey())], &[&creator]).await;
+
+    assert_eq!(cancelled.len(), 1);
+    assert_eq!((cancelled[0].raffle, cancelled[0].cancelled_by), (raffle.raffle, creator.pubkey()));
+    assert!(!cancelled[0].undersold && !cancelled[0].timed_out);
+    assert_eq!(cancelled[0].tickets_purchased, 5);
+
+    let mut refunded = 0;
+
+    for (buyer, tickets) in buyers.iter().zip([3, 2]) {
+        let claimed = emitted::<RefundClaimed>(&mut env, &[raffle.claim_refund(&buyer.pubkey())], &[buyer]).await;
+        refunded += tickets;
+
+        assert_eq!(claimed.len(), 1);
+        assert_eq!((claimed[0].raffle, claimed[0].buyer), (raffle.raffle, buyer.pubkey()));
+        assert_eq!((claimed[0].tickets, claimed[0].amount), (tickets, tickets * PRICE));
+        assert_eq!(claimed[0].tickets_refunded, refunded);
+    }
+}
+
+#[tokio::test]
+async fn missed_reveal_cancels_at_the_timeout() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+
+    env.process(&[raffle.buy(&buyer.pubkey(), 2)], &[&buyer]).await.unwrap();
+    env.end_raffle(raffle_data(now).end).await;
+    env.process(&[raffle.lock(&buyer.pubkey())], &[&buyer]).await.unwrap();
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    env.warp_to(account.locked_at + raffler_anchor::randomness::REVEAL_TIMEOUT).await;
+
+    let cancelled = emitted::<RaffleCancelled>(&mut env, &[raffle.set_winner_by(&buyer.pubkey())], &[&buyer]).await;
+
+    assert_eq!(cancelled.len(), 1);
+    assert_eq!(cancelled[0].cancelled_by, buyer.pubkey());
+    assert!(cancelled[0].timed_out && !cancelled[0].undersold);
+}
+
+#[tokio::test]
+async fn oracle_randomness() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let oracle = env.funded_keypair(1_000_000_000).await;
+    let data = CreateRaffleData {
+        source: RandomnessSource::Oracle,
+        oracle: oracle.pubkey(),
+        ..raffle_data(now)
+    };
+    let raffle = TestRaffle::create(&mut env, data).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+
+    env.process(&[raffle.buy(&buyer.pubkey(), 2)], &[&buyer]).await.unwrap();
+    env.end_raffle(raffle_data(now).end).await;
+    env.process(&[raffle.lock(&buyer.pubkey())], &[&buyer]).await.unwrap();
+
+    let requested = emitted::<RandomnessRequested>(&mut env, &[raffle.request_randomness(&buyer.pubkey())], &[&buyer]).await;
+    let request: RandomnessRequest = env.account(&raffle.randomness_request()).await;
+
+    assert_eq!(requested.len(), 1);
+    assert_eq!((requested[0].raffle, requested[0].request), (raffle.raffle, raffle.randomness_request()));
+    assert_eq!((requested[0].oracle, requested[0].alpha), (oracle.pubkey(), request.alpha));
+
+    let (ed25519, proof) = ed25519_instruction(&oracle, &request.alpha);
+    let fulfilled = emitted::<RandomnessFulfilled>(&mut env, &[ed25519, raffle.fulfill_randomness(&oracle.pubkey(), proof)], &[&oracle]).await;
+    let request: RandomnessRequest = env.account(&raffle.randomness_request()).await;
+
+    assert_eq!(fulfilled.len(), 1);
+    assert_eq!((fulfilled[0].raffle, fulfilled[0].request), (raffle.raffle, raffle.randomness_request()));
+    assert_eq!((fulfilled[0].output, fulfilled[0].fulfilled_slot), (request.output, request.fulfilled_slot));
+
+    // the seed comes in with the first draw once the slot after the fulfilment has passed
+    env.pass_entropy_delay().await;
+
+    let revealed = emitted::<SeedRevealed>(&mut env, &[raffle.set_winner_by(&buyer.pubkey())], &[&buyer]).await;
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(revealed.len(), 1);
+    assert_eq!(revealed[0].source, RandomnessSource::Oracle);
+    assert_eq!((revealed[0].slot_hash, revealed[0].seed), (account.slot_hash, account.seed));
+}
+
+#[tokio::test]
+async fn fee_tiers_and_bundles() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let admin = env.admin.insecure_clone();
+
+    let set_fee = raffle.set_fee(&env, 250);
+    let fee = emitted::<FeeSet>(&mut env, &[set_fee], &[&admin]).await;
+
+    assert_eq!(fee.len(), 1);
+    assert_eq!((fee[0].raffle, fee[0].admin, fee[0].fee_bps), (raffle.raffle, admin.pubkey(), 250));
+
+    let tier_mint = env.create_mint(0).await;
+    env.fund_tokens(&creator.pubkey(), &tier_mint, 4).await;
+
+    let tier = PrizeTierData { quantity: 4, per_win: 2, decimals: 0 };
+    let added = emitted::<PrizeTierAdded>(&mut env, &[raffle.add_tier(&tier_mint, tier)], &[&creator]).await;
+
+    assert_eq!(added.len(), 1);
+    assert_eq!((added[0].raffle, added[0].tier, added[0].mint), (raffle.raffle, 1, tier_mint));
+    assert_eq!((added[0].quantity, added[0].per_win, added[0].decimals), (4, 2, 0));
+
+    let mut bundle = vec![];
+
+    for _ in 0..2 {
+        let mint = env.create_mint(0).await;
+        env.fund_tokens(&creator.pubkey(), &mint, 1).await;
+        bundle.push(mint);
+    }
+
+    let bundled = emitted::<BundleAdded>(&mut env, &[raffle.add_bundle(&bundle[..1])], &[&creator]).await;
+    assert_eq!((bundled[0].mints.clone(), bundled[0].bundle_len), (bundle[..1].to_vec(), 1));
+
+    let bundled = emitted::<BundleAdded>(&mut env, &[raffle.add_bundle(&bundle[1..])], &[&creator]).await;
+    assert_eq!((bundled[0].mints.clone(), bundled[0].bundle_len), (bundle[1..].to_vec(), 2));
+
+    // nothing sold and nothing paid, the whole tier goes back
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    let close_tier = raffle.close_tier(&env, &tier_mint, 1, false);
+    let closed = emitted::<PrizeTierClosed>(&mut env, &[close_tier], &[&creator]).await;
+
+    assert_eq!(closed.len(), 1);
+    assert_eq!((closed[0].raffle, closed[0].tier, closed[0].mint), (raffle.raffle, 1, tier_mint));
+    assert_eq!((closed[0].closed_by, closed[0].returned), (creator.pubkey(), 4));
+}
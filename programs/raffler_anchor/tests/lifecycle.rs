@@ -0,0 +1,446 @@
+mod common;
+
+use {
+    common::*,
+    anchor_lang::AnchorSerialize,
+    raffler_anchor::{error::CustomError, randomness::*, state::*},
+    solana_sdk::{rent::Rent, signature::Keypair, signer::Signer},
+};
+
+fn spl_data(now: i64, burn: bool) -> CreateRaffleData {
+    CreateRaffleData {
+        native: false,
+        burn,
+        ..drawn_data(now)
+    }
+}
+
+// the creator pays every winner through draw_winner, last slot first
+async fn pay_winners(env: &mut TestEnv, raffle: &TestRaffle, buyers: &[Keypair; 2]) {
+    let creator = raffle.creator.insecure_clone();
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    for ticket in account.winners.iter().rev() {
+        let winner = buyer_of(buyers, *ticket).pubkey();
+
+        if env.context.banks_client.get_account(ata(&winner, &raffle.mint_prize)).await.unwrap().is_none() {
+            env.create_ata(&winner, &raffle.mint_prize).await;
+        }
+
+        env.process(&[raffle.draw_winner(&winner)], &[&creator]).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn native_raffle_runs_start_to_finish() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let buyers = buy_in(&mut env, &raffle).await;
+    let vault = vault_address(&raffle.raffle);
+
+    assert_eq!(env.lamports(&vault).await, Rent::default().minimum_balance(0) + 5 * PRICE);
+    assert_eq!(env.token_balance(&raffle.escrow_prize).await, 2);
+
+    let account = raffle.draw(&mut env, SECRET).await;
+
+    assert_ne!(account.slot_hash, [0; 32]);
+    assert_eq!(account.seed, reveal_seed(&SECRET, &account.slot_hash));
+    assert_eq!(account.winners.len(), 2);
+    assert_ne!(account.winners[0], account.winners[1]);
+    assert_eq!(account.claimed, vec![false, false]);
+
+    // the first winner claims on their own, the creator pays the other
+    let first = buyer_of(&buyers, account.winners[0]).insecure_clone();
+    env.process(&[raffle.claim_prize(&first.pubkey(), &first.pubkey(), account.winners[0])], &[&first]).await.unwrap();
+
+    let second = buyer_of(&buyers, account.winners[1]).pubkey();
+
+    if env.context.banks_client.get_account(ata(&second, &raffle.mint_prize)).await.unwrap().is_none() {
+        env.create_ata(&second, &raffle.mint_prize).await;
+    }
+
+    let creator = raffle.creator.insecure_clone();
+    env.process(&[raffle.draw_winner(&second)], &[&creator]).await.unwrap();
+
+    for buyer in &buyers {
+        let won = account.winners.iter().filter(|ticket| buyer_of(&buyers, **ticket).pubkey() == buyer.pubkey()).count() as u64;
+
+        if won > 0 {
+            assert_eq!(env.token_balance(&ata(&buyer.pubkey(), &raffle.mint_prize)).await, won);
+        }
+    }
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    assert_eq!((account.claimed, account.sent_out), (vec![true, true], 2));
+
+    let before = env.lamports(&creator.pubkey()).await;
+    let vault_balance = env.lamports(&vault).await;
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    // the proceeds and the vault's rent come back together, the ticket account's header rent less the protocol cut
+    let header_rent = Rent::default().minimum_balance(RAFFLE_ENTRY_OFFSET);
+
+    assert_eq!(env.lamports(&creator.pubkey()).await - before, vault_balance + header_rent - header_rent / 10);
+    assert_eq!(env.lamports(&vault).await, 0);
+    assert_eq!(env.lamports(&raffle.raffle).await, 0);
+    assert_eq!(env.lamports(&raffle.escrow_prize).await, 0);
+    assert_eq!(env.lamports(&raffle.tickets).await, 0);
+}
+
+#[tokio::test]
+async fn spl_proceeds_are_returned() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, spl_data(now, false)).await;
+    let creator = raffle.creator.insecure_clone();
+    let admin = env.admin.insecure_clone();
+
+    env.process(&[raffle.set_fee(&env, 250)], &[&admin]).await.unwrap();
+    let buyers = buy_in(&mut env, &raffle).await;
+
+    assert_eq!(env.token_balance(&ata(&raffle.raffle, &raffle.mint_cost)).await, 5 * PRICE);
+
+    raffle.draw(&mut env, SECRET).await;
+    pay_winners(&mut env, &raffle, &buyers).await;
+
+    let supply = env.supply(&raffle.mint_cost).await;
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    let fee = 5 * PRICE * 250 / 10_000;
+
+    assert_eq!(env.token_balance(&ata(&creator.pubkey(), &raffle.mint_cost)).await, 5 * PRICE - fee);
+    assert_eq!(env.token_balance(&ata(&env.fee_recipient.pubkey(), &raffle.mint_cost)).await, fee);
+    assert_eq!(env.supply(&raffle.mint_cost).await, supply);
+    assert_eq!(env.lamports(&ata(&raffle.raffle, &raffle.mint_cost)).await, 0);
+    assert_eq!(env.token_balance(&raffle.token_prize).await, 0);
+}
+
+#[tokio::test]
+async fn spl_proceeds_are_burned() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, spl_data(now, true)).await;
+    let creator = raffle.creator.insecure_clone();
+    let admin = env.admin.insecure_clone();
+
+    env.process(&[raffle.set_fee(&env, 250)], &[&admin]).await.unwrap();
+    let buyers = buy_in(&mut env, &raffle).await;
+
+    raffle.draw(&mut env, SECRET).await;
+    pay_winners(&mut env, &raffle, &buyers).await;
+
+    let supply = env.supply(&raffle.mint_cost).await;
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    // the fee is paid before the burn, everything else is gone
+    let fee = 5 * PRICE * 250 / 10_000;
+
+    assert_eq!(env.token_balance(&ata(&creator.pubkey(), &raffle.mint_cost)).await, 0);
+    assert_eq!(env.token_balance(&ata(&env.fee_recipient.pubkey(), &raffle.mint_cost)).await, fee);
+    assert_eq!(supply - env.supply(&raffle.mint_cost).await, 5 * PRICE - fee);
+    assert_eq!(env.lamports(&ata(&raffle.raffle, &raffle.mint_cost)).await, 0);
+}
+
+#[tokio::test]
+async fn admin_force_close_skips_the_burn() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, spl_data(now, true)).await;
+    buy_in(&mut env, &raffle).await;
+    let creator = raffle.creator.insecure_clone();
+    let admin = env.admin.insecure_clone();
+
+    raffle.draw(&mut env, SECRET).await;
+
+    // nobody was paid, so the creator can't close
+    assert_error(env.process(&[raffle.close(&env, false)], &[&creator]).await, CustomError::RaffleStarted);
+
+    // and only the admin can force it
+    let mut forced_by_creator = raffle.close(&env, true);
+    forced_by_creator.accounts[0].pubkey = creator.pubkey();
+    assert_error(env.process(&[forced_by_creator], &[&creator]).await, CustomError::InputError);
+
+    let supply = env.supply(&raffle.mint_cost).await;
+
+    env.process(&[raffle.close(&env, true)], &[&admin]).await.unwrap();
+
+    // the prize and the proceeds go back to the creator, nothing is burned
+    assert_eq!(env.token_balance(&raffle.token_prize).await, 2);
+    assert_eq!(env.token_balance(&ata(&creator.pubkey(), &raffle.mint_cost)).await, 5 * PRICE);
+    assert_eq!(env.supply(&raffle.mint_cost).await, supply);
+    assert_eq!(env.lamports(&raffle.raffle).await, 0);
+}
+
+#[tokio::test]
+async fn admin_force_close_pays_native_proceeds_to_the_creator() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    buy_in(&mut env, &raffle).await;
+    let creator = raffle.creator.insecure_clone();
+    let admin = env.admin.insecure_clone();
+    let vault = vault_address(&raffle.raffle);
+
+    raffle.draw(&mut env, SECRET).await;
+
+    let before = env.lamports(&creator.pubkey()).await;
+    let admin_before = env.lamports(&admin.pubkey()).await;
+    let vault_balance = env.lamports(&vault).await;
+
+    env.process(&[raffle.close(&env, true)], &[&admin]).await.unwrap();
+
+    // the admin only pays the transaction fee, everything the raffle held goes to the creator
+    let header_rent = Rent::default().minimum_balance(RAFFLE_ENTRY_OFFSET);
+
+    assert_eq!(env.lamports(&creator.pubkey()).await - before, vault_balance + header_rent - header_rent / 10);
+    assert!(env.lamports(&admin.pubkey()).await <= admin_before);
+    assert_eq!(env.lamports(&vault).await, 0);
+}
+
+#[tokio::test]
+async fn refunds_after_a_cancel() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, spl_data(now, false)).await;
+    let buyers = buy_in(&mut env, &raffle).await;
+    let creator = raffle.creator.insecure_clone();
+
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    for (buyer, amount) in buyers.iter().zip([3, 2]) {
+        env.process(&[raffle.claim_refund(&buyer.pubkey())], &[buyer]).await.unwrap();
+        assert_eq!(env.token_balance(&ata(&buyer.pubkey(), &raffle.mint_cost)).await, amount * PRICE);
+    }
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    assert_eq!(env.token_balance(&ata(&creator.pubkey(), &raffle.mint_cost)).await, 0);
+    assert_eq!(env.token_balance(&raffle.token_prize).await, 2);
+}
+
+#[tokio::test]
+async fn refunds_when_the_secret_never_comes() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let buyers = buy_in(&mut env, &raffle).await;
+    let creator = raffle.creator.insecure_clone();
+
+    env.end_raffle(drawn_data(now).end).await;
+    env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    // the creator never reveals
+    assert_error(env.process(&[raffle.set_winner_by(&buyers[0].pubkey())], &[&buyers[0]]).await, CustomError::RevealPending);
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    env.warp_to(account.locked_at + REVEAL_TIMEOUT).await;
+
+    // any buyer can move it on, nobody draws from a seed someone already knows
+    env.process(&[raffle.set_winner_by(&buyers[0].pubkey())], &[&buyers[0]]).await.unwrap();
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    assert!(account.refunding && account.winners.is_empty());
+
+    for buyer in &buyers {
+        env.process(&[raffle.claim_refund(&buyer.pubkey())], &[buyer]).await.unwrap();
+    }
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+    assert_eq!(env.token_balance(&raffle.token_prize).await, 2);
+}
+
+#[tokio::test]
+async fn draws_from_an_oracle() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let oracle = env.funded_keypair(1_000_000_000).await;
+    let data = CreateRaffleData {
+        source: RandomnessSource::Oracle,
+        oracle: oracle.pubkey(),
+        ..drawn_data(now)
+    };
+    let raffle = TestRaffle::create(&mut env, data).await;
+    buy_in(&mut env, &raffle).await;
+    let creator = raffle.creator.insecure_clone();
+
+    env.end_raffle(drawn_data(now).end).await;
+    env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await.unwrap();
+    env.process(&[raffle.request_randomness(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    assert_error(env.process(&[raffle.set_winner()], &[&creator]).await, CustomError::RevealPending);
+
+    let request: RandomnessRequest = env.account(&raffle.randomness_request()).await;
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(request.alpha, oracle_alpha(&raffle.raffle, account.lock_slot));
+
+    let (ed25519, proof) = ed25519_instruction(&oracle, &request.alpha);
+    env.process(&[ed25519, raffle.fulfill_randomness(&oracle.pubkey(), proof)], &[&oracle]).await.unwrap();
+
+    // the output is in, the slot it gets mixed with isn't yet
+    assert_error(env.process(&[raffle.set_winner()], &[&creator]).await, CustomError::RevealPending);
+    env.pass_entropy_delay().await;
+
+    while !env.account::<RaffleAccount>(&raffle.raffle).await.winners_selected {
+        env.process(&[raffle.set_winner()], &[&creator]).await.unwrap();
+    }
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    assert_ne!(account.slot_hash, [0; 32]);
+    assert_eq!(account.seed, oracle_seed(&oracle_output(&proof), &account.slot_hash));
+
+    // it's fulfilled once
+    let (ed25519, proof) = ed25519_instruction(&oracle, &request.alpha);
+    assert_error(env.process(&[ed25519, raffle.fulfill_randomness(&oracle.pubkey(), proof)], &[&oracle]).await, CustomError::InputError);
+}
+
+#[tokio::test]
+async fn extra_tiers_are_drawn_after_the_main_prize() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let tier_mint = env.create_mint(0).await;
+
+    env.fund_tokens(&creator.pubkey(), &tier_mint, 3).await;
+    env.process(&[raffle.add_tier(&tier_mint, PrizeTierData { quantity: 3, per_win: 3, decimals: 0 })], &[&creator]).await.unwrap();
+
+    assert_eq!(env.token_balance(&ata(&raffle.raffle, &tier_mint)).await, 3);
+
+    buy_in(&mut env, &raffle).await;
+
+    let account = raffle.draw(&mut env, SECRET).await;
+
+    assert_eq!(account.winners.len(), 3);
+    assert_eq!(account.slot_prize(2).unwrap().mint, tier_mint);
+
+
+    // the tier's winner hasn't been paid, only the admin can take it back
+    assert_error(env.process(&[raffle.close_tier(&env, &tier_mint, 1, false)], &[&creator]).await, CustomError::CantScam);
+
+    let admin = env.admin.insecure_clone();
+    env.process(&[raffle.close_tier(&env, &tier_mint, 1, true)], &[&admin]).await.unwrap();
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert!(account.tiers[1].closed);
+    assert_eq!(env.token_balance(&ata(&creator.pubkey(), &tier_mint)).await, 3);
+    assert_eq!(env.lamports(&ata(&raffle.raffle, &tier_mint)).await, 0);
+}
+
+#[tokio::test]
+async fn raffles_grow_to_hold_every_winner() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { prize_quantity: 150, win_multiple: true, ..drawn_data(now) }).await;
+
+    // created at 1200 bytes, grown to its worst case with the creator paying the rent
+    let size = env.data(&raffle.raffle).await.len();
+
+    assert!(size > 1200);
+    assert_eq!(env.lamports(&raffle.raffle).await, Rent::default().minimum_balance(size));
+
+    buy_in(&mut env, &raffle).await;
+
+    let account = raffle.draw(&mut env, SECRET).await;
+
+    assert_eq!(account.winners.len(), 150);
+    assert_eq!(account.space(), size);
+
+    // all that's left unused is what a token gate would take over this open one
+    assert_eq!(8 + account.try_to_vec().unwrap().len() + 32 + 8, size);
+}
+
+// nfts for a bundle, each held by the creator
+async fn bundle_nfts(env: &mut TestEnv, raffle: &TestRaffle, count: usize) -> Vec<solana_sdk::pubkey::Pubkey> {
+    let mut bundle = vec![];
+
+    for _ in 0..count {
+        let mint = env.create_mint(0).await;
+        env.fund_tokens(&raffle.creator.pubkey(), &mint, 1).await;
+        bundle.push(mint);
+    }
+
+    bundle
+}
+
+#[tokio::test]
+async fn bundle_nfts_are_drawn_after_the_tiers() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let bundle = bundle_nfts(&mut env, &raffle, 2).await;
+
+    env.process(&[raffle.add_bundle(&bundle)], &[&creator]).await.unwrap();
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(account.bundle, bundle);
+    assert!(env.data(&raffle.raffle).await.len() >= account.space());
+
+    for mint in &bundle {
+        assert_eq!(env.token_balance(&ata(&raffle.raffle, mint)).await, 1);
+        assert_eq!(env.token_balance(&ata(&creator.pubkey(), mint)).await, 0);
+    }
+
+    let buyers = buy_in(&mut env, &raffle).await;
+    let account = raffle.draw(&mut env, SECRET).await;
+
+    assert_eq!(account.winners.len(), 4);
+    assert_eq!(account.slot_prize(2).unwrap().mint, bundle[0]);
+    assert_eq!(account.slot_prize(3).unwrap().mint, bundle[1]);
+
+    // every winner claims their own slot's prize, the bundle ones with the nft's mint
+    for (slot, ticket) in account.winners.iter().enumerate() {
+        let winner = buyer_of(&buyers, *ticket).insecure_clone();
+        let mint = account.slot_prize(slot).unwrap().mint;
+
+        env.process(&[raffle.claim_prize_of(&winner.pubkey(), &winner.pubkey(), *ticket, &mint)], &[&winner]).await.unwrap();
+    }
+
+    for (index, mint) in bundle.iter().enumerate() {
+        let winner = buyer_of(&buyers, account.winners[account.bundle_slot(index)]).pubkey();
+        assert_eq!(env.token_balance(&ata(&winner, mint)).await, 1);
+    }
+
+    // the bundle escrows are empty and closed with the raffle
+    assert_error(env.process(&[raffle.close(&env, false)], &[&creator]).await, CustomError::InputError);
+    env.process(&[raffle.close_with_bundle(&env, false, &bundle)], &[&creator]).await.unwrap();
+
+    for mint in &bundle {
+        assert_eq!(env.lamports(&ata(&raffle.raffle, mint)).await, 0);
+        assert_eq!(env.token_balance(&ata(&creator.pubkey(), mint)).await, 0);
+    }
+}
+
+#[tokio::test]
+async fn unwon_bundle_nfts_go_back_on_close() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, drawn_data(now)).await;
+    let creator = raffle.creator.insecure_clone();
+    let bundle = bundle_nfts(&mut env, &raffle, 3).await;
+
+    env.process(&[raffle.add_bundle(&bundle[..2])], &[&creator]).await.unwrap();
+    env.process(&[raffle.add_bundle(&bundle[2..])], &[&creator]).await.unwrap();
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    // the escrow and creator account swapped, the old close order
+    let mut swapped = raffle.close_with_bundle(&env, false, &bundle);
+    let bundle_start = swapped.accounts.len() - 9;
+    swapped.accounts[bundle_start..].chunks_mut(3).for_each(|accounts| accounts.swap(1, 2));
+
+    assert_error(env.process(&[swapped], &[&creator]).await, CustomError::InputError);
+
+    env.process(&[raffle.close_with_bundle(&env, false, &bundle)], &[&creator]).await.unwrap();
+
+    for mint in &bundle {
+        assert_eq!(env.token_balance(&ata(&creator.pubkey(), mint)).await, 1);
+        assert_eq!(env.lamports(&ata(&raffle.raffle, mint)).await, 0);
+    }
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use {
+    anchor_lang::{error::ErrorCode, Discriminator},
+    common::*,
+    raffler_anchor::{error::CustomError, state::*, tickets::ticket_runs},
+    solana_sdk::{account::AccountSharedData, instruction::Instruction, pubkey::Pubkey, signer::Signer},
+};
+
+// the same purchase pointed at some other ticket account
+fn with_tickets(mut instruction: Instruction, from: &Pubkey, to: &Pubkey) -> Instruction {
+    for meta in instruction.accounts.iter_mut().filter(|meta| meta.pubkey == *from) {
+        meta.pubkey = *to;
+    }
+
+    instruction
+}
+
+#[tokio::test]
+async fn create_links_ticket_account() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+
+    let data = env.data(&raffle.tickets).await;
+    let header: &FixedTicketAccount = bytemuck::from_bytes(&data[8 .. RAFFLE_ENTRY_OFFSET]);
+
+    assert_eq!(&data[..8], &FixedTicketAccount::DISCRIMINATOR);
+    assert_eq!({ header.raffle_id }, raffle.raffle);
+    assert_eq!({ header.count }, 0);
+    assert_eq!(data.len(), RAFFLE_ENTRY_OFFSET);
+}
+
+#[tokio::test]
+async fn runs_are_typed_in_place() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let a = env.funded_keypair(1_000_000_000).await;
+    let b = env.funded_keypair(1_000_000_000).await;
+
+    env.process(&[raffle.buy(&a.pubkey(), 2)], &[&a]).await.unwrap();
+    env.process(&[raffle.buy(&b.pubkey(), 3)], &[&b]).await.unwrap();
+
+    let data = env.data(&raffle.tickets).await;
+    let runs = ticket_runs(&data);
+
+    assert_eq!(data.len(), RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * 2);
+    assert_eq!(runs.len(), 2);
+    assert_eq!(({ runs[0].buyer }, { runs[0].end }), (a.pubkey(), 2));
+    assert_eq!(({ runs[1].buyer }, { runs[1].end }), (b.pubkey(), 5));
+}
+
+#[tokio::test]
+async fn rejects_another_raffles_tickets() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let first = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let second = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+
+    let buy = with_tickets(first.buy(&buyer.pubkey(), 1), &first.tickets, &second.tickets);
+    assert_error(env.process(&[buy], &[&buyer]).await, CustomError::InputError);
+
+    // both ticket accounts are untouched
+    for tickets in [first.tickets, second.tickets] {
+        let data = env.data(&tickets).await;
+        assert!(ticket_runs(&data).is_empty());
+    }
+}
+
+#[tokio::test]
+async fn rejects_foreign_owner() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+
+    // a byte for byte copy of the real account, just owned by someone else
+    let data = env.data(&raffle.tickets).await;
+    let forged = Pubkey::new_unique();
+    let mut account = AccountSharedData::new(1_000_000_000, data.len(), &Pubkey::new_unique());
+    account.set_data_from_slice(&data);
+    env.context.set_account(&forged, &account);
+
+    let buy = with_tickets(raffle.buy(&buyer.pubkey(), 1), &raffle.tickets, &forged);
+    assert_error(env.process(&[buy], &[&buyer]).await, ErrorCode::AccountOwnedByWrongProgram);
+}
+
+#[tokio::test]
+async fn rejects_other_account_types() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let first = env.funded_keypair(1_000_000_000).await;
+    let second = env.funded_keypair(1_000_000_000).await;
+
+    env.process(&[raffle.buy(&first.pubkey(), 1)], &[&first]).await.unwrap();
+
+    // owned by the program, but a buyer entry rather than tickets
+    let entry = entry_address(&raffle.raffle, &first.pubkey());
+    let buy = with_tickets(raffle.buy(&second.pubkey(), 1), &raffle.tickets, &entry);
+    assert_error(env.process(&[buy], &[&second]).await, ErrorCode::AccountDiscriminatorMismatch);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use {
+    anchor_lang::error::ErrorCode,
+    common::*,
+    raffler_anchor::{error::CustomError, state::*},
+    solana_sdk::{rent::Rent, signature::Keypair, signer::Signer},
+};
+
+fn fixed_data(now: i64) -> CreateRaffleData {
+    CreateRaffleData {
+        fixed: true,
+        max_entries: 10,
+        ..raffle_data(now)
+    }
+}
+
+fn run_rent(runs: usize) -> u64 {
+    Rent::default().minimum_balance(RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * runs) - Rent::default().minimum_balance(RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * (runs - 1))
+}
+
+#[tokio::test]
+async fn fixed_raffles_start_at_the_header() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+
+    // preallocating for every ticket is no longer accepted
+    let result = TestRaffle::try_create(&mut env, fixed_data(now), RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * 10).await;
+    assert_error(result.map(|_| ()), CustomError::FixedError);
+
+    let raffle = TestRaffle::create(&mut env, fixed_data(now)).await;
+
+    assert_eq!(env.data(&raffle.tickets).await.len(), RAFFLE_ENTRY_OFFSET);
+    assert_eq!(env.lamports(&raffle.tickets).await, Rent::default().minimum_balance(RAFFLE_ENTRY_OFFSET));
+}
+
+#[tokio::test]
+async fn buyers_pay_for_the_runs_they_add() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, fixed_data(now)).await;
+    let a = env.funded_keypair(1_000_000_000).await;
+    let b = env.funded_keypair(1_000_000_000).await;
+    let entry_rent = Rent::default().minimum_balance(8 + 32 + 32 + 8 + 8 + 8 + 1);
+
+    let before = env.lamports(&a.pubkey()).await;
+    env.process(&[raffle.buy(&a.pubkey(), 2)], &[&a]).await.unwrap();
+    assert_eq!(before - env.lamports(&a.pubkey()).await, 2 * PRICE + entry_rent + run_rent(1));
+
+    // extending the last run is free
+    let before = env.lamports(&a.pubkey()).await;
+    env.process(&[raffle.buy(&a.pubkey(), 1)], &[&a]).await.unwrap();
+    assert_eq!(before - env.lamports(&a.pubkey()).await, PRICE);
+
+    env.process(&[raffle.buy(&b.pubkey(), 4)], &[&b]).await.unwrap();
+    env.process(&[raffle.buy(&a.pubkey(), 3)], &[&a]).await.unwrap();
+
+    let a_entry: BuyerEntry = env.account(&entry_address(&raffle.raffle, &a.pubkey())).await;
+    let b_entry: BuyerEntry = env.account(&entry_address(&raffle.raffle, &b.pubkey())).await;
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(a_entry.rent_paid, run_rent(1) + run_rent(3));
+    assert_eq!(b_entry.rent_paid, run_rent(2));
+    assert_eq!(account.buyer_rent, a_entry.rent_paid + b_entry.rent_paid);
+
+    assert_eq!(env.data(&raffle.tickets).await.len(), RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * 3);
+    assert_eq!(env.lamports(&raffle.tickets).await, Rent::default().minimum_balance(RAFFLE_ENTRY_OFFSET + RAFFLE_ENTRY_SIZE * 3));
+
+    // the cap still holds with nothing preallocated
+    assert_error(env.process(&[raffle.buy(&b.pubkey(), 2)], &[&b]).await, CustomError::NotEnough);
+}
+
+#[tokio::test]
+async fn close_gives_buyer_rent_back() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, fixed_data(now)).await;
+    let buyers: Vec<Keypair> = vec![env.funded_keypair(1_000_000_000).await, env.funded_keypair(1_000_000_000).await];
+
+    for (buyer, amount) in buyers.iter().zip([2, 3]) {
+        env.process(&[raffle.buy(&buyer.pubkey(), amount)], &[buyer]).await.unwrap();
+    }
+
+    let creator = raffle.creator.insecure_clone();
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    for buyer in &buyers {
+        env.process(&[raffle.claim_refund(&buyer.pubkey())], &[buyer]).await.unwrap();
+    }
+
+    // nothing to take back while the raffle is still around
+    assert_error(env.process(&[raffle.close_entry(&buyers[0].pubkey())], &[&buyers[0]]).await, CustomError::RaffleOpen);
+
+    let config_before = env.lamports(&config_address()).await;
+    let creator_before = env.lamports(&creator.pubkey()).await;
+    let fee_before = env.lamports(&env.fee_recipient.pubkey()).await;
+    let raffle_rent = env.lamports(&raffle.raffle).await;
+    let vault_rent = env.lamports(&vault_address(&raffle.raffle)).await;
+    let escrow_rent = env.lamports(&raffle.escrow_prize).await;
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    // the creator's share is just the header they paid for, less the protocol's cut
+    let header_rent = Rent::default().minimum_balance(RAFFLE_ENTRY_OFFSET);
+    let fee = header_rent / 10;
+    let buyer_rent = run_rent(1) + run_rent(2);
+
+    assert_eq!(env.lamports(&raffle.tickets).await, 0);
+    assert_eq!(env.lamports(&config_address()).await - config_before, buyer_rent);
+    assert_eq!(env.lamports(&creator.pubkey()).await - creator_before, header_rent - fee + vault_rent);
+    assert_eq!(env.lamports(&env.fee_recipient.pubkey()).await - fee_before, raffle_rent + escrow_rent + fee);
+
+    let entry_rent = Rent::default().minimum_balance(8 + 32 + 32 + 8 + 8 + 8 + 1);
+
+    for (buyer, runs) in buyers.iter().zip([1, 2]) {
+        let before = env.lamports(&buyer.pubkey()).await;
+        env.process(&[raffle.close_entry(&buyer.pubkey())], &[buyer]).await.unwrap();
+
+        assert_eq!(env.lamports(&buyer.pubkey()).await - before, entry_rent + run_rent(runs));
+        assert_eq!(env.lamports(&entry_address(&raffle.raffle, &buyer.pubkey())).await, 0);
+    }
+
+    assert_eq!(env.lamports(&config_address()).await, config_before);
+}
+
+#[tokio::test]
+async fn entries_close_once_and_only_for_their_buyer() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, fixed_data(now)).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+    let other = env.funded_keypair(1_000_000_000).await;
+
+    env.process(&[raffle.buy(&buyer.pubkey(), 1)], &[&buyer]).await.unwrap();
+
+    let creator = raffle.creator.insecure_clone();
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+    env.process(&[raffle.claim_refund(&buyer.pubkey())], &[&buyer]).await.unwrap();
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    // someone else's wallet doesn't derive to the buyer's entry
+    let mut steal = raffle.close_entry(&other.pubkey());
+    steal.accounts[2].pubkey = entry_address(&raffle.raffle, &buyer.pubkey());
+    assert_error(env.process(&[steal], &[&other]).await, ErrorCode::ConstraintSeeds);
+
+    env.process(&[raffle.close_entry(&buyer.pubkey())], &[&buyer]).await.unwrap();
+
+    // the entry is gone, so is the rent it recorded
+    let again = env.process(&[raffle.close_entry(&buyer.pubkey())], &[&buyer]).await;
+    assert_error(again, ErrorCode::AccountNotInitialized);
+}
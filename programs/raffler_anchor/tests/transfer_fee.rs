@@ -0,0 +1,129 @@
+mod common;
+
+use {
+    common::*,
+    raffler_anchor::state::*,
+    solana_sdk::signer::Signer,
+};
+
+const FEE_BPS: u16 = 100;
+
+// what the token program holds back on a transfer of `amount`, rounded up like it does
+fn transfer_fee(amount: u64) -> u64 {
+    (amount * FEE_BPS as u64).div_ceil(10_000)
+}
+
+// two winners of 100 out of five tickets, both mints take 1% of every transfer
+fn fee_data(now: i64) -> CreateRaffleData {
+    CreateRaffleData {
+        prize_quantity: 200,
+        per_win: 100,
+        native: false,
+        ..drawn_data(now)
+    }
+}
+
+#[tokio::test]
+async fn escrows_hold_exact_amounts_with_transfer_fees() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create_with_transfer_fee(&mut env, fee_data(now), FEE_BPS).await;
+    let creator = raffle.creator.insecure_clone();
+    let admin = env.admin.insecure_clone();
+
+    // the creator covered the fee on the deposit, the escrow holds the whole prize
+    let deposited = 400 - env.token_balance(&raffle.token_prize).await;
+
+    assert_eq!(env.token_balance(&raffle.escrow_prize).await, 200);
+    assert_eq!(deposited - transfer_fee(deposited), 200);
+    assert_eq!(env.withheld(&raffle.escrow_prize).await, deposited - 200);
+
+    env.process(&[raffle.set_fee(&env, 250)], &[&admin]).await.unwrap();
+
+    let buyers = [env.funded_keypair(1_000_000_000).await, env.funded_keypair(1_000_000_000).await];
+    let mut paid = 0;
+
+    for (buyer, amount) in buyers.iter().zip([3, 2]) {
+        let token_cost = env.fund_tokens(&buyer.pubkey(), &raffle.mint_cost, 2 * amount * PRICE).await;
+
+        env.process(&[raffle.buy(&buyer.pubkey(), amount)], &[buyer]).await.unwrap();
+
+        // the buyer pays the ticket price plus the fee on it
+        let gross = 2 * amount * PRICE - env.token_balance(&token_cost).await;
+
+        assert_eq!(gross - transfer_fee(gross), amount * PRICE);
+        paid += gross;
+    }
+
+    let escrow_cost = raffle.token_cost(&raffle.raffle).unwrap();
+
+    assert_eq!(env.token_balance(&escrow_cost).await, 5 * PRICE);
+    assert_eq!(env.withheld(&escrow_cost).await, paid - 5 * PRICE);
+
+    let account = raffle.draw(&mut env, SECRET).await;
+
+    // winners take the fee on the way out
+    for (slot, ticket) in account.winners.iter().enumerate() {
+        let winner = buyer_of(&buyers, *ticket).insecure_clone();
+        let token_prize = token_address(&winner.pubkey(), &raffle.mint_prize, &raffle.token_program);
+
+        if slot == 0 {
+            env.process(&[raffle.claim_prize(&winner.pubkey(), &winner.pubkey(), *ticket)], &[&winner]).await.unwrap();
+        } else {
+            if env.context.banks_client.get_account(token_prize).await.unwrap().is_none() {
+                env.create_ata(&winner.pubkey(), &raffle.mint_prize).await;
+            }
+
+            env.process(&[raffle.draw_winner(&winner.pubkey())], &[&creator]).await.unwrap();
+        }
+
+        let won = account.winners[..=slot].iter().filter(|won| buyer_of(&buyers, **won).pubkey() == winner.pubkey()).count() as u64;
+
+        assert_eq!(env.token_balance(&token_prize).await, won * (100 - transfer_fee(100)));
+    }
+
+    assert_eq!(env.token_balance(&raffle.escrow_prize).await, 0);
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    let fee = 5 * PRICE * 250 / 10_000;
+    let proceeds = 5 * PRICE - fee;
+
+    assert_eq!(env.token_balance(&raffle.token_cost(&creator.pubkey()).unwrap()).await, proceeds - transfer_fee(proceeds));
+    assert_eq!(env.token_balance(&raffle.token_cost(&env.fee_recipient.pubkey()).unwrap()).await, fee - transfer_fee(fee));
+
+    // the escrows could only close once their withheld fees were harvested to the mints
+    assert_eq!(env.lamports(&escrow_cost).await, 0);
+    assert_eq!(env.lamports(&raffle.escrow_prize).await, 0);
+    assert_eq!(env.mint_withheld(&raffle.mint_cost).await, paid - 5 * PRICE);
+    assert_eq!(env.mint_withheld(&raffle.mint_prize).await, deposited - 200);
+}
+
+#[tokio::test]
+async fn refunds_return_the_net_ticket_price() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create_with_transfer_fee(&mut env, fee_data(now), FEE_BPS).await;
+    let creator = raffle.creator.insecure_clone();
+    let buyer = env.funded_keypair(1_000_000_000).await;
+
+    let token_cost = env.fund_tokens(&buyer.pubkey(), &raffle.mint_cost, 4 * PRICE).await;
+    env.process(&[raffle.buy(&buyer.pubkey(), 2)], &[&buyer]).await.unwrap();
+
+    let left = env.token_balance(&token_cost).await;
+
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+    env.process(&[raffle.claim_refund(&buyer.pubkey())], &[&buyer]).await.unwrap();
+
+    // the escrow sends back what it holds for the tickets, the fee on the way back comes off it
+    assert_eq!(env.token_balance(&raffle.token_cost(&raffle.raffle).unwrap()).await, 0);
+    assert_eq!(env.token_balance(&token_cost).await - left, 2 * PRICE - transfer_fee(2 * PRICE));
+
+    let before = env.token_balance(&raffle.token_prize).await;
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    // the whole prize goes back to the creator, less the fee on that transfer
+    assert_eq!(env.token_balance(&raffle.token_prize).await - before, 200 - transfer_fee(200));
+    assert_eq!(env.lamports(&raffle.escrow_prize).await, 0);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use {
+    common::*,
+    raffler_anchor::{error::CustomError, state::*, tickets::ticket_runs},
+    solana_sdk::{signature::Keypair, signer::Signer},
+    std::collections::BTreeSet,
+};
+
+async fn distinct_buyers(env: &mut TestEnv, raffle: &TestRaffle) -> usize {
+    let data = env.data(&raffle.tickets).await;
+    ticket_runs(&data).iter().map(|run| run.buyer).collect::<BTreeSet<_>>().len()
+}
+
+#[tokio::test]
+async fn unique_entries_counts_distinct_buyers() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+
+    let mut buyers: Vec<Keypair> = vec![];
+
+    for _ in 0..3 {
+        buyers.push(env.funded_keypair(1_000_000_000).await);
+    }
+
+    // interleaved so the same buyer shows up in several runs
+    for (buyer, amount) in [(0, 1), (1, 2), (0, 3), (2, 1), (1, 1), (0, 2)] {
+        let buyer = &buyers[buyer];
+        env.process(&[raffle.buy(&buyer.pubkey(), amount)], &[buyer]).await.unwrap();
+    }
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(account.tickets_purchased, 10);
+    assert_eq!(account.unique_entries, 3);
+    assert_eq!(account.unique_entries as usize, distinct_buyers(&mut env, &raffle).await);
+
+    for (buyer, tickets) in buyers.iter().zip([6, 3, 1]) {
+        let entry: BuyerEntry = env.account(&entry_address(&raffle.raffle, &buyer.pubkey())).await;
+
+        assert_eq!(entry.buyer, buyer.pubkey());
+        assert_eq!(entry.raffle, raffle.raffle);
+        assert_eq!(entry.tickets, tickets);
+    }
+}
+
+#[tokio::test]
+async fn repeat_purchases_count_once() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+
+    for amount in 1..=5 {
+        env.process(&[raffle.buy(&buyer.pubkey(), amount)], &[&buyer]).await.unwrap();
+    }
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+    let data = env.data(&raffle.tickets).await;
+    let runs = ticket_runs(&data);
+
+    assert_eq!(account.unique_entries, 1);
+    assert_eq!(account.tickets_purchased, 15);
+
+    // back to back purchases extend the one run
+    assert_eq!(runs.len(), 1);
+    assert_eq!({ runs[0].end }, 15);
+}
+
+#[tokio::test]
+async fn many_buyers_stay_exact() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+
+    let mut buyers: Vec<Keypair> = vec![];
+
+    for _ in 0..12 {
+        buyers.push(env.funded_keypair(1_000_000_000).await);
+    }
+
+    // every buyer twice, the second round shouldn't move the count
+    for round in 0..2 {
+        for buyer in &buyers {
+            env.process(&[raffle.buy(&buyer.pubkey(), 1 + round)], &[buyer]).await.unwrap();
+        }
+    }
+
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(account.unique_entries, 12);
+    assert_eq!(account.unique_entries as usize, distinct_buyers(&mut env, &raffle).await);
+}
+
+#[tokio::test]
+async fn empty_purchases_are_rejected() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, raffle_data(now)).await;
+    let buyer = env.funded_keypair(1_000_000_000).await;
+
+    assert_error(env.process(&[raffle.buy(&buyer.pubkey(), 0)], &[&buyer]).await, CustomError::InputError);
+
+    // no entry, no run, and the wallet isn't counted
+    let account: RaffleAccount = env.account(&raffle.raffle).await;
+
+    assert_eq!(account.unique_entries, 0);
+    assert_eq!(distinct_buyers(&mut env, &raffle).await, 0);
+    assert!(env.context.banks_client.get_account(entry_address(&raffle.raffle, &buyer.pubkey())).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn entries_start_over_when_the_raffle_is_created_again() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, CreateRaffleData { max_tickets_per_wallet: 3, ..raffle_data(now) }).await;
+    let creator = raffle.creator.insecure_clone();
+    let buyers = [env.funded_keypair(1_000_000_000).await, env.funded_keypair(1_000_000_000).await];
+
+    for buyer in &buyers {
+        env.process(&[raffle.buy(&buyer.pubkey(), 3)], &[buyer]).await.unwrap();
+    }
+
+    env.process(&[raffle.cancel(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    for buyer in &buyers {
+        env.process(&[raffle.claim_refund(&buyer.pubkey())], &[buyer]).await.unwrap();
+    }
+
+    env.process(&[raffle.close(&env, false)], &[&creator]).await.unwrap();
+
+    env.warp_to(now + 60).await;
+    let now = env.now().await;
+    let again = raffle.recreate(&mut env, CreateRaffleData { max_tickets_per_wallet: 3, ..raffle_data(now) }).await;
+
+    assert_eq!(again.raffle, raffle.raffle);
+
+    // the cap counts from zero, the first raffle's tickets are gone with it
+    env.process(&[again.buy(&buyers[0].pubkey(), 3)], &[&buyers[0]]).await.unwrap();
+
+    let account: RaffleAccount = env.account(&again.raffle).await;
+    let entry: BuyerEntry = env.account(&entry_address(&again.raffle, &buyers[0].pubkey())).await;
+
+    assert_eq!(account.unique_entries, 1);
+    assert_eq!((entry.tickets, entry.raffle_created), (3, account.date_created));
+
+    // the other buyer's entry still belongs to the closed raffle, so it can be closed with the new one open
+    env.process(&[again.close_entry(&buyers[1].pubkey())], &[&buyers[1]]).await.unwrap();
+    assert_error(env.process(&[again.close_entry(&buyers[0].pubkey())], &[&buyers[0]]).await, CustomError::RaffleOpen);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use {
+    common::*,
+    raffler_anchor::state::*,
+    raffler_client::{accounts::{decode_raffle, decode_tickets}, verify::Mismatch, verify_draw, RandomnessInput},
+    solana_sdk::{signature::Keypair, signer::Signer},
+};
+
+// three winners out of ten tickets from three buyers, see buy_ten
+fn verified_data(now: i64) -> CreateRaffleData {
+    CreateRaffleData {
+        prize_quantity: 3,
+        ..drawn_data(now)
+    }
+}
+
+async fn buy_ten(env: &mut TestEnv, raffle: &TestRaffle) -> Vec<Keypair> {
+    let mut buyers = vec![];
+
+    for amount in [4, 1, 5] {
+        let buyer = env.funded_keypair(1_000_000_000).await;
+        env.process(&[raffle.buy(&buyer.pubkey(), amount)], &[&buyer]).await.unwrap();
+        buyers.push(buyer);
+    }
+
+    buyers
+}
+
+// the raffle and its ticket account as an rpc would hand them out
+async fn fetch(env: &mut TestEnv, raffle: &TestRaffle) -> (RaffleAccount, raffler_client::TicketAccount) {
+    let account = decode_raffle(&env.data(&raffle.raffle).await).unwrap();
+    let tickets = decode_tickets(&env.data(&raffle.tickets).await).unwrap();
+
+    (account, tickets)
+}
+
+#[tokio::test]
+async fn a_drawn_raffle_verifies_from_its_secret() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let raffle = TestRaffle::create(&mut env, verified_data(now)).await;
+    let buyers = buy_ten(&mut env, &raffle).await;
+
+    raffle.draw(&mut env, SECRET).await;
+
+    let (account, tickets) = fetch(&mut env, &raffle).await;
+    let verification = verify_draw(&account, &tickets, RandomnessInput::Secret(SECRET));
+
+    assert!(verification.is_valid(), "{:?}", verification.mismatches);
+    assert_eq!(verification.seed, account.seed);
+    assert_eq!(verification.winners.iter().map(|winner| winner.ticket).collect::<Vec<_>>(), account.winners);
+
+    // every winner is pinned to the wallet that bought the ticket
+    for winner in &verification.winners {
+        let buyer = match winner.ticket {
+            0..=3 => &buyers[0],
+            4 => &buyers[1],
+            _ => &buyers[2],
+        };
+
+        assert_eq!(winner.buyer, Some(buyer.pubkey()));
+    }
+
+    // a secret the creator didn't commit to is caught
+    let verification = verify_draw(&account, &tickets, RandomnessInput::Secret([8; 32]));
+
+    assert!(verification.mismatches.contains(&Mismatch::Commitment));
+    assert!(verification.mismatches.iter().any(|mismatch| matches!(mismatch, Mismatch::Seed { .. })));
+}
+
+#[tokio::test]
+async fn an_oracle_draw_verifies_from_its_proof() {
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    let oracle = env.funded_keypair(1_000_000_000).await;
+    let data = CreateRaffleData {
+        source: RandomnessSource::Oracle,
+        oracle: oracle.pubkey(),
+        ..verified_data(now)
+    };
+    let raffle = TestRaffle::create(&mut env, data).await;
+    let creator = raffle.creator.insecure_clone();
+
+    buy_ten(&mut env, &raffle).await;
+
+    env.end_raffle(verified_data(now).end).await;
+    env.process(&[raffle.lock(&creator.pubkey())], &[&creator]).await.unwrap();
+    env.process(&[raffle.request_randomness(&creator.pubkey())], &[&creator]).await.unwrap();
+
+    let request: RandomnessRequest = env.account(&raffle.randomness_request()).await;
+    let (ed25519, proof) = ed25519_instruction(&oracle, &request.alpha);
+
+    env.process(&[ed25519, raffle.fulfill_randomness(&oracle.pubkey(), proof)], &[&oracle]).await.unwrap();
+    env.pass_entropy_delay().await;
+
+    while !env.account::<RaffleAccount>(&raffle.raffle).await.winners_selected {
+        env.process(&[raffle.set_winner()], &[&creator]).await.unwrap();
+    }
+
+    let (account, tickets) = fetch(&mut env, &raffle).await;
+    let verification = verify_draw(&account, &tickets, RandomnessInput::OracleProof(proof));
+
+    assert!(verification.is_valid(), "{:?}", verification.mismatches);
+    assert_eq!(verification.winners.len(), 3);
+
+    // a proof from anyone but the raffle's oracle doesn't verify
+    let (_, forged) = ed25519_instruction(&Keypair::new(), &request.alpha);
+    let verification = verify_draw(&account, &tickets, RandomnessInput::OracleProof(forged));
+
+    assert!(verification.mismatches.contains(&Mismatch::OracleProof));
+}